@@ -0,0 +1,91 @@
+//! Compares the memory used to chunk and serialize a large broadcast when messages are
+//! cloned into owned chunks versus serialized from borrowed slices.
+//!
+//! Run with `cargo bench --bench chunk_allocations`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use expo_push_notification_client::{
+    ExpoPushMessage, SendPushNotificationsRequest, TryIntoSendPushNotificationsRequest,
+};
+
+struct CountingAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        TOTAL.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const MESSAGES: usize = 100_000;
+const CHUNK_SIZE: usize = 100;
+
+fn measure<F: FnOnce()>(name: &str, f: F) {
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    TOTAL.store(0, Ordering::Relaxed);
+    f();
+    println!(
+        "{name:>8}: peak {:>12} bytes above baseline, {:>12} bytes allocated in total",
+        PEAK.load(Ordering::Relaxed) - baseline,
+        TOTAL.load(Ordering::Relaxed)
+    );
+}
+
+fn main() -> anyhow::Result<()> {
+    let messages = (0..MESSAGES)
+        .map(|i| {
+            ExpoPushMessage::builder([format!("ExponentPushToken[{i:0>22}]")])
+                .title("title")
+                .body("body")
+                .build()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Mirrors the previous request path, which took ownership of the messages, cloned them
+    // in `messages()` and cloned every chunk again with `to_vec()`.
+    let owned = messages.clone();
+    measure("cloned", || {
+        let request = owned
+            .try_into_send_push_notifications_request()
+            .expect("messages are not empty");
+        let chunks = request
+            .messages()
+            .to_vec()
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| SendPushNotificationsRequest::from(chunk.to_vec()))
+            .collect::<Vec<_>>();
+        for chunk in chunks {
+            std::hint::black_box(serde_json::to_vec(&chunk).expect("serializable"));
+        }
+    });
+
+    measure("borrowed", || {
+        let request = messages
+            .as_slice()
+            .try_into_send_push_notifications_request()
+            .expect("messages are not empty");
+        for chunk in request.messages().chunks(CHUNK_SIZE) {
+            let chunk = SendPushNotificationsRequest::from(chunk);
+            std::hint::black_box(serde_json::to_vec(&chunk).expect("serializable"));
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,433 @@
+//! Sends push notifications and looks up receipts from the command line.
+//!
+//! The access token is read from `EXPO_ACCESS_TOKEN`.
+
+use std::{collections::HashMap, process::ExitCode};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use expo_push_notification_client::{
+    Expo, ExpoClientOptions, ExpoPushErrorReceipt, ExpoPushMessage, ExpoPushReceipt,
+    ExpoPushReceiptId, ExpoPushTicket, Priority, Sound,
+};
+use serde::Serialize;
+
+#[derive(Parser)]
+#[command(name = "expo-push", version, about)]
+struct Cli {
+    #[arg(long, env = "EXPO_ACCESS_TOKEN", hide_env_values = true)]
+    access_token: Option<String>,
+    #[arg(long, env = "EXPO_BASE_URL")]
+    base_url: Option<String>,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Table,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a notification and print its tickets.
+    Send {
+        /// Recipient token, repeat for several recipients.
+        #[arg(long = "to", required = true)]
+        to: Vec<String>,
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long)]
+        body: Option<String>,
+        /// JSON object delivered to the app.
+        #[arg(long)]
+        data: Option<String>,
+        #[arg(long, value_parser = ["default", "normal", "high"])]
+        priority: Option<String>,
+        /// `default` or the name of a custom sound.
+        #[arg(long)]
+        sound: Option<String>,
+        /// Time to live in seconds.
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+    /// Print the receipts of the given receipt ids.
+    Receipts {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Check that tokens look like Expo push tokens.
+    ValidateToken {
+        #[arg(required = true)]
+        tokens: Vec<String>,
+    },
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Row {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receipt_id: Option<String>,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl Row {
+    fn with_error(mut self, error: &ExpoPushErrorReceipt) -> Self {
+        self.error = error
+            .details
+            .as_ref()
+            .and_then(|details| details.error.as_ref())
+            .map(|error| error.as_str().to_string());
+        self.message = Some(error.message.clone());
+        self
+    }
+
+    fn cells(&self) -> [String; 5] {
+        [
+            self.token.clone().unwrap_or_default(),
+            self.receipt_id.clone().unwrap_or_default(),
+            self.status.clone(),
+            self.error.clone().unwrap_or_default(),
+            self.message.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = run(&cli).await;
+    match &result {
+        Ok(rows) => match render(cli.format, rows) {
+            Ok(output) => print!("{output}"),
+            Err(e) => eprintln!("error: {e}"),
+        },
+        Err(e) => eprintln!("error: {e}"),
+    }
+    ExitCode::from(exit_code(&result))
+}
+
+/// 0 when every row is ok, 1 when some are not and 2 when the request failed.
+fn exit_code(result: &Result<Vec<Row>, Box<dyn std::error::Error>>) -> u8 {
+    match result {
+        Ok(rows) if rows.iter().all(|row| row.status == "ok") => 0,
+        Ok(_) => 1,
+        Err(_) => 2,
+    }
+}
+
+async fn run(cli: &Cli) -> Result<Vec<Row>, Box<dyn std::error::Error>> {
+    let expo = Expo::new(ExpoClientOptions {
+        access_token: cli.access_token.clone(),
+        base_url: cli.base_url.clone(),
+        ..Default::default()
+    });
+    match &cli.command {
+        Command::Send {
+            to,
+            title,
+            body,
+            data,
+            priority,
+            sound,
+            ttl,
+        } => {
+            let mut builder = ExpoPushMessage::builder(to);
+            if let Some(title) = title {
+                builder = builder.title(title);
+            }
+            if let Some(body) = body {
+                builder = builder.body(body);
+            }
+            if let Some(data) = data {
+                builder = builder.data(&serde_json::from_str::<serde_json::Value>(data)?)?;
+            }
+            if let Some(priority) = priority {
+                builder = builder.priority(Priority::from(priority.clone()));
+            }
+            if let Some(sound) = sound {
+                builder = builder.sound(match sound.as_str() {
+                    "default" => Sound::Default,
+                    _ => Sound::Custom(sound.clone()),
+                });
+            }
+            if let Some(ttl) = ttl {
+                builder = builder.ttl(*ttl);
+            }
+            let tickets = expo.send_push_notifications(builder.build()?).await?;
+            Ok(ticket_rows(to, tickets))
+        }
+        Command::Receipts { ids } => {
+            let receipts = expo.get_push_notification_receipts(ids.clone()).await?;
+            Ok(receipt_rows(ids, receipts))
+        }
+        Command::ValidateToken { tokens } => Ok(token_rows(tokens)),
+    }
+}
+
+fn ticket_rows(to: &[String], tickets: Vec<ExpoPushTicket>) -> Vec<Row> {
+    to.iter()
+        .zip(tickets)
+        .map(|(token, ticket)| {
+            let row = Row {
+                token: Some(token.clone()),
+                ..Default::default()
+            };
+            match ticket {
+                ExpoPushTicket::Ok(ticket) => Row {
+                    receipt_id: Some(ticket.id.to_string()),
+                    status: "ok".to_string(),
+                    ..row
+                },
+                ExpoPushTicket::Error(error) => Row {
+                    status: "error".to_string(),
+                    ..row
+                }
+                .with_error(&error),
+                ExpoPushTicket::Unknown(status) => Row { status, ..row },
+            }
+        })
+        .collect()
+}
+
+fn receipt_rows(
+    ids: &[String],
+    mut receipts: HashMap<ExpoPushReceiptId, ExpoPushReceipt>,
+) -> Vec<Row> {
+    ids.iter()
+        .map(|id| {
+            let row = Row {
+                receipt_id: Some(id.clone()),
+                ..Default::default()
+            };
+            let receipt = id.parse().ok().and_then(|id| receipts.remove(&id));
+            match receipt {
+                Some(ExpoPushReceipt::Ok) => Row {
+                    status: "ok".to_string(),
+                    ..row
+                },
+                Some(ExpoPushReceipt::Error(error)) => Row {
+                    status: "error".to_string(),
+                    ..row
+                }
+                .with_error(&error),
+                Some(ExpoPushReceipt::Unknown(status)) => Row { status, ..row },
+                None => Row {
+                    status: "missing".to_string(),
+                    ..row
+                },
+            }
+        })
+        .collect()
+}
+
+fn token_rows(tokens: &[String]) -> Vec<Row> {
+    tokens
+        .iter()
+        .map(|token| {
+            let valid = Expo::is_expo_push_token(token);
+            Row {
+                token: Some(token.clone()),
+                status: if valid { "ok" } else { "invalid" }.to_string(),
+                message: (!valid).then(|| "not an Expo push token".to_string()),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn render(format: Format, rows: &[Row]) -> Result<String, serde_json::Error> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(rows)? + "\n"),
+        Format::Table => {
+            let header = ["TOKEN", "RECEIPT ID", "STATUS", "ERROR", "MESSAGE"].map(String::from);
+            let rows = rows.iter().map(Row::cells).collect::<Vec<_>>();
+            // Empty columns are left out.
+            let columns = (0..header.len())
+                .filter(|&column| rows.iter().any(|row| !row[column].is_empty()))
+                .map(|column| {
+                    let width = std::iter::once(&header)
+                        .chain(&rows)
+                        .map(|row| row[column].chars().count())
+                        .max()
+                        .unwrap_or(0);
+                    (column, width)
+                })
+                .collect::<Vec<_>>();
+            let mut output = String::new();
+            for row in std::iter::once(&header).chain(&rows) {
+                let line = columns
+                    .iter()
+                    .map(|&(column, width)| format!("{:width$}", row[column]))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                output.push_str(line.trim_end());
+                output.push('\n');
+            }
+            Ok(output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: &str = "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]";
+    const Y: &str = "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]";
+    const RECEIPT_ID: &str = "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX";
+
+    fn cli(url: &str, args: &[&str]) -> Cli {
+        Cli::try_parse_from(["expo-push", "--base-url", url].iter().chain(args))
+            .expect("arguments are valid")
+    }
+
+    #[tokio::test]
+    async fn test_send() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "data": [
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            {
+                "status": "error",
+                "message": "not registered",
+                "details": { "error": "DeviceNotRegistered" }
+            }
+        ]
+    }
+    "#,
+            )
+            .create();
+
+        let result = run(&cli(
+            &server.url(),
+            &["send", "--to", X, "--to", Y, "--title", "hi"],
+        ))
+        .await;
+        let rows = result.as_ref().map_err(|e| anyhow::anyhow!("{e}"))?;
+        assert_eq!(
+            rows,
+            &[
+                Row {
+                    token: Some(X.to_string()),
+                    receipt_id: Some(RECEIPT_ID.to_string()),
+                    status: "ok".to_string(),
+                    ..Default::default()
+                },
+                Row {
+                    token: Some(Y.to_string()),
+                    status: "error".to_string(),
+                    error: Some("DeviceNotRegistered".to_string()),
+                    message: Some("not registered".to_string()),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(exit_code(&result), 1);
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_request_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(500)
+            .create();
+
+        let result = run(&cli(&server.url(), &["send", "--to", X])).await;
+        assert_eq!(exit_code(&result), 2);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_receipts() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": { "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX": { "status": "ok" } } }"#,
+            )
+            .create();
+        let missing = "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY";
+
+        let result = run(&cli(&server.url(), &["receipts", RECEIPT_ID, missing])).await;
+        let rows = result.as_ref().map_err(|e| anyhow::anyhow!("{e}"))?;
+        assert_eq!(
+            rows.iter()
+                .map(|row| (row.receipt_id.as_deref(), row.status.as_str()))
+                .collect::<Vec<_>>(),
+            [(Some(RECEIPT_ID), "ok"), (Some(missing), "missing")]
+        );
+        assert_eq!(exit_code(&result), 1);
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_token() -> anyhow::Result<()> {
+        let result = run(&cli("http://localhost", &["validate-token", X])).await;
+        assert_eq!(exit_code(&result), 0);
+
+        let result = run(&cli("http://localhost", &["validate-token", X, "invalid"])).await;
+        let rows = result.as_ref().map_err(|e| anyhow::anyhow!("{e}"))?;
+        assert_eq!(rows[1].status, "invalid");
+        assert_eq!(exit_code(&result), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_table() -> anyhow::Result<()> {
+        let rows = [
+            Row {
+                token: Some("ExponentPushToken[x]".to_string()),
+                receipt_id: Some("id".to_string()),
+                status: "ok".to_string(),
+                ..Default::default()
+            },
+            Row {
+                token: Some("invalid".to_string()),
+                status: "invalid".to_string(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            render(Format::Table, &rows)?,
+            "TOKEN                 RECEIPT ID  STATUS\n\
+             ExponentPushToken[x]  id          ok\n\
+             invalid                           invalid\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_json() -> anyhow::Result<()> {
+        let rows = [Row {
+            token: Some(X.to_string()),
+            status: "ok".to_string(),
+            ..Default::default()
+        }];
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&render(Format::Json, &rows)?)?,
+            serde_json::json!([{ "token": X, "status": "ok" }])
+        );
+        Ok(())
+    }
+}
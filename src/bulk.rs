@@ -0,0 +1,300 @@
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CustomError, ExpoPushMessage, ExpoPushTicket, LineError};
+
+/// Reads one [`ExpoPushMessage`] per line of JSON Lines, skipping blank lines.
+///
+/// Messages are read lazily; a line that cannot be read or is not a valid message yields a
+/// [`LineError`] and does not stop the iteration.
+pub fn read_jsonl<R>(reader: R) -> impl Iterator<Item = Result<ExpoPushMessage, LineError>>
+where
+    R: BufRead,
+{
+    reader.lines().enumerate().filter_map(|(index, text)| {
+        let line = index + 1;
+        match text {
+            Ok(text) if text.trim().is_empty() => None,
+            Ok(text) => Some(
+                serde_json::from_str::<ExpoPushMessage>(&text)
+                    .map_err(|e| LineError::new(line, e.to_string())),
+            ),
+            Err(e) => Some(Err(LineError::new(line, e.to_string()))),
+        }
+    })
+}
+
+/// A row of a CSV audience: the `token` column and the other columns by header.
+#[cfg(feature = "csv")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvRecipient {
+    pub token: String,
+    pub variables: std::collections::HashMap<String, String>,
+}
+
+/// Reads a CSV audience with a header row and a `token` column, turning each row into a
+/// message with `to_message`.
+///
+/// Rows are read lazily; a row that cannot be read or converted yields a [`LineError`] and
+/// does not stop the iteration.
+#[cfg(feature = "csv")]
+pub fn read_csv<R, F>(
+    reader: R,
+    mut to_message: F,
+) -> impl Iterator<Item = Result<ExpoPushMessage, LineError>>
+where
+    R: std::io::Read,
+    F: FnMut(CsvRecipient) -> Result<ExpoPushMessage, crate::ValidationError>,
+{
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader
+        .headers()
+        .cloned()
+        .map_err(|e| LineError::new(1, e.to_string()))
+        .and_then(|headers| match headers.iter().position(|h| h == "token") {
+            Some(token_column) => Ok((headers, token_column)),
+            None => Err(LineError::new(1, "missing `token` column".to_string())),
+        });
+    let (headers, token_column, header_error) = match headers {
+        Ok((headers, token_column)) => (headers, token_column, None),
+        Err(e) => (csv::StringRecord::new(), 0, Some(e)),
+    };
+    let records = header_error
+        .is_none()
+        .then(|| reader.into_records())
+        .into_iter()
+        .flatten();
+    header_error
+        .map(Err)
+        .into_iter()
+        .chain(records.map(move |record| {
+            let record = record.map_err(|e| {
+                let line = e.position().map_or(0, |position| position.line() as usize);
+                LineError::new(line, e.to_string())
+            })?;
+            let line = record
+                .position()
+                .map_or(0, |position| position.line() as usize);
+            let mut recipient = CsvRecipient::default();
+            for (column, (header, value)) in headers.iter().zip(record.iter()).enumerate() {
+                if column == token_column {
+                    recipient.token = value.to_string();
+                } else {
+                    recipient
+                        .variables
+                        .insert(header.to_string(), value.to_string());
+                }
+            }
+            to_message(recipient).map_err(|e| LineError::new(line, e.to_string()))
+        }))
+}
+
+/// The outcome of sending to one token, as written by [`ResultWriter`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendResult {
+    pub token: String,
+    /// `ok`, `error`, or a status not known to this crate.
+    pub status: String,
+    pub receipt_id: Option<String>,
+    /// `details.error` of a failed ticket.
+    pub error: Option<String>,
+    pub message: Option<String>,
+}
+
+impl SendResult {
+    /// `tickets` must be in the order returned for `messages`, one per recipient.
+    pub fn from_tickets(messages: &[ExpoPushMessage], tickets: &[ExpoPushTicket]) -> Vec<Self> {
+        messages
+            .iter()
+            .flat_map(|message| message.to())
+            .zip(tickets)
+            .map(|(token, ticket)| {
+                let mut result = SendResult {
+                    token: token.clone(),
+                    status: String::new(),
+                    receipt_id: None,
+                    error: None,
+                    message: None,
+                };
+                match ticket {
+                    ExpoPushTicket::Ok(ticket) => {
+                        result.status = "ok".to_string();
+                        result.receipt_id = Some(ticket.id.to_string());
+                    }
+                    ExpoPushTicket::Error(error) => {
+                        result.status = "error".to_string();
+                        result.error = error
+                            .details
+                            .as_ref()
+                            .and_then(|details| details.error.as_ref())
+                            .map(|error| error.as_str().to_string());
+                        result.message = Some(error.message.clone());
+                    }
+                    ExpoPushTicket::Unknown(status) => result.status = status.clone(),
+                }
+                result
+            })
+            .collect()
+    }
+}
+
+/// Writes [`SendResult`]s as JSON Lines or CSV for auditing.
+pub enum ResultWriter<W: Write> {
+    Jsonl(W),
+    #[cfg(feature = "csv")]
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> ResultWriter<W> {
+    pub fn jsonl(writer: W) -> Self {
+        ResultWriter::Jsonl(writer)
+    }
+
+    /// Writes a header row before the first result.
+    #[cfg(feature = "csv")]
+    pub fn csv(writer: W) -> Self {
+        ResultWriter::Csv(Box::new(csv::Writer::from_writer(writer)))
+    }
+
+    pub fn write(&mut self, result: &SendResult) -> Result<(), CustomError> {
+        match self {
+            ResultWriter::Jsonl(writer) => serde_json::to_writer(&mut *writer, result)
+                .map_err(|e| CustomError::SerializeErr(e.to_string()))
+                .and_then(|_| {
+                    writer
+                        .write_all(b"\n")
+                        .map_err(|e| CustomError::SerializeErr(e.to_string()))
+                }),
+            #[cfg(feature = "csv")]
+            ResultWriter::Csv(writer) => writer
+                .serialize(result)
+                .map_err(|e| CustomError::SerializeErr(e.to_string())),
+        }
+    }
+
+    /// Writes the results of a chunk, see [`SendResult::from_tickets`].
+    pub fn write_tickets(
+        &mut self,
+        messages: &[ExpoPushMessage],
+        tickets: &[ExpoPushTicket],
+    ) -> Result<(), CustomError> {
+        for result in SendResult::from_tickets(messages, tickets) {
+            self.write(&result)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(self) -> Result<W, CustomError> {
+        match self {
+            ResultWriter::Jsonl(writer) => Ok(writer),
+            #[cfg(feature = "csv")]
+            ResultWriter::Csv(writer) => writer
+                .into_inner()
+                .map_err(|e| CustomError::SerializeErr(e.to_string())),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), CustomError> {
+        match self {
+            ResultWriter::Jsonl(writer) => writer.flush(),
+            #[cfg(feature = "csv")]
+            ResultWriter::Csv(writer) => writer.flush(),
+        }
+        .map_err(|e| CustomError::SerializeErr(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_jsonl() {
+        let input = r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],"title":"a"}
+
+{"to":["invalid"]}
+not json
+{"to":"ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]","title":"b"}
+"#;
+        let results = read_jsonl(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[0].as_ref().map(|message| message.title()),
+            Ok(Some("a"))
+        );
+        assert_eq!(results[1].as_ref().map_err(|e| e.line), Err(3));
+        assert_eq!(results[2].as_ref().map_err(|e| e.line), Err(4));
+        assert_eq!(
+            results[3].as_ref().map(|message| message.to()),
+            Ok(&["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn test_write_jsonl() -> anyhow::Result<()> {
+        let messages = [ExpoPushMessage::builder([
+            "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+            "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+        ])
+        .build()?];
+        let tickets = serde_json::from_value::<Vec<ExpoPushTicket>>(serde_json::json!([
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            { "status": "error", "message": "unregistered", "details": { "error": "DeviceNotRegistered" } }
+        ]))?;
+
+        let mut writer = ResultWriter::jsonl(vec![]);
+        writer.write_tickets(&messages, &tickets)?;
+        assert_eq!(
+            String::from_utf8(writer.into_inner()?)?,
+            r#"{"token":"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","status":"ok","receiptId":"XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX","error":null,"message":null}
+{"token":"ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]","status":"error","receiptId":null,"error":"DeviceNotRegistered","message":"unregistered"}
+"#
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_read_and_write_csv() -> anyhow::Result<()> {
+        let input = "token,name\nExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx],Alice\ninvalid,Bob\n";
+        let results = read_csv(input.as_bytes(), |recipient| {
+            ExpoPushMessage::builder([recipient.token])
+                .title(format!("Hi {}", recipient.variables["name"]))
+                .build()
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            results[0].as_ref().map(|message| message.title()),
+            Ok(Some("Hi Alice"))
+        );
+        assert_eq!(
+            results[1]
+                .as_ref()
+                .map_err(|e| (e.line, e.message.as_str())),
+            Err((3, "Invalid token"))
+        );
+
+        let missing = read_csv("to\nx\n".as_bytes(), |_| unreachable!()).collect::<Vec<_>>();
+        assert_eq!(
+            missing,
+            vec![Err(LineError::new(1, "missing `token` column".to_string()))]
+        );
+
+        let mut writer = ResultWriter::csv(vec![]);
+        writer.write(&SendResult {
+            token: "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]".to_string(),
+            status: "ok".to_string(),
+            receipt_id: Some("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX".to_string()),
+            error: None,
+            message: None,
+        })?;
+        assert_eq!(
+            String::from_utf8(writer.into_inner()?)?,
+            "token,status,receiptId,error,message\nExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx],ok,XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX,,\n"
+        );
+        Ok(())
+    }
+}
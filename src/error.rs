@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, PartialEq, thiserror::Error, Eq, Hash)]
+#[derive(Clone, Debug, Deserialize, PartialEq, thiserror::Error, Eq, Hash)]
 pub enum CustomError {
     #[error("Gzip error: {0}")]
     GzipErr(String),
@@ -12,6 +12,8 @@ pub enum CustomError {
     SerializeErr(String),
     #[error("Server error: {0}")]
     ServerErr(String),
+    #[error("Store error: {0}")]
+    StoreErr(String),
 }
 
 impl std::convert::From<std::convert::Infallible> for CustomError {
@@ -26,6 +28,33 @@ pub enum ValidationError {
     InvalidData,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Invalid ttl")]
+    InvalidTtl,
+    #[error("Invalid expiration")]
+    InvalidExpiration,
+    #[error("Invalid url")]
+    InvalidUrl,
+    #[error("Invalid template")]
+    InvalidTemplate,
+    #[error("Missing variable: {0}")]
+    MissingVariable(String),
+    #[error("Missing translation: {0}")]
+    MissingTranslation(String),
+}
+
+/// An input line that could not be read as a message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Line {line}: {message}")]
+pub struct LineError {
+    /// 1-based.
+    pub line: usize,
+    pub message: String,
+}
+
+impl LineError {
+    pub fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
 }
 
 #[cfg(test)]
@@ -1,21 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use async_compression::tokio::write::GzipEncoder;
+use futures::{Stream, StreamExt as _};
 use reqwest::{
     header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE},
     Method,
 };
-use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     error::CustomError,
+    gzip::{self, GzipPolicy},
     object::{
-        ExpoPushMessage, ExpoPushReceipt, ExpoPushTicket, SendPushNotificationsRequest,
-        TryIntoSendPushNotificationsRequest,
+        DetailsErrorType, ExpoPushErrorReceipt, ExpoPushMessage, ExpoPushReceipt, ExpoPushTicket,
+        InvalidToken, PushChunkResult, ReceiptPollOptions, ReceiptPollReport, SerializeMessages,
+        TicketRetryOptions, TryIntoSendPushNotificationsRequest,
     },
+    receipt_store::{PendingReceipt, ReceiptStore},
+    sent_key_store::SentKeyStore,
     ExpoPushReceiptId,
 };
 
+// Expo accepts at most 100 messages per request.
+const CHUNK_SIZE: usize = 100;
+// Expo accepts at most 1000 receipt ids per request.
+const RECEIPT_IDS_CHUNK_SIZE: usize = 1000;
+
 #[derive(Debug, PartialEq, serde::Deserialize)]
 struct SendPushNotificationSuccessfulResponse {
     data: Vec<ExpoPushTicket>,
@@ -26,29 +37,100 @@ pub struct Expo {
     access_token: Option<String>,
     base_url: String,
     client: reqwest::Client,
+    compact_to: bool,
+    gzip: GzipPolicy,
+    gzip_level: Option<u32>,
+    receipt_store: Option<Arc<dyn ReceiptStore>>,
+    on_invalid_token: Option<UnboundedSender<InvalidToken>>,
+    on_store_error: Option<UnboundedSender<CustomError>>,
+    on_retry_error: Option<UnboundedSender<CustomError>>,
+    ticket_retry: Option<TicketRetryOptions>,
+    sent_keys: Option<Arc<dyn SentKeyStore>>,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Default)]
 pub struct ExpoClientOptions {
     pub access_token: Option<String>,
+    /// Defaults to `https://exp.host`.
+    pub base_url: Option<String>,
+    /// Send `to` as a string instead of an array for single-recipient messages.
+    pub compact_to: bool,
+    /// When request bodies are gzip-compressed. Defaults to bodies longer than 1024 bytes.
+    pub gzip: GzipPolicy,
+    /// Compression level from 0 to 9. Defaults to 6.
+    pub gzip_level: Option<u32>,
+    /// Records the receipt id and token of every `ExpoPushTicket::Ok` when set.
+    pub receipt_store: Option<Arc<dyn ReceiptStore>>,
+    /// Receives every token reported as `DeviceNotRegistered` by a ticket or a receipt, so
+    /// that it can be removed from the token registry.
+    pub on_invalid_token: Option<UnboundedSender<InvalidToken>>,
+    /// Receives the errors of `receipt_store` and `sent_keys` written after Expo accepted a
+    /// chunk. The tickets are returned anyway, since failing the send would make a retrying
+    /// caller send the chunk twice.
+    pub on_store_error: Option<UnboundedSender<CustomError>>,
+    /// Resends the notifications whose ticket failed with a retryable error when set. The
+    /// final tickets keep the order of the messages.
+    pub ticket_retry: Option<TicketRetryOptions>,
+    /// Receives the errors of the resend requests made for `ticket_retry`. The tickets of
+    /// the notifications a failed resend carried are returned as they were before it.
+    pub on_retry_error: Option<UnboundedSender<CustomError>>,
+    /// Skips the messages whose idempotency key was already sent and returns their original
+    /// tickets instead, when set. A key repeated within a request is sent once.
+    ///
+    /// A key is recorded only after Expo accepted its chunk, so a crash in between still
+    /// sends the message again.
+    pub sent_keys: Option<Arc<dyn SentKeyStore>>,
 }
 
-impl Expo {
-    pub fn new(options: ExpoClientOptions) -> Self {
-        Self::new_with_base_url(options.access_token, "https://exp.host")
+impl std::fmt::Debug for ExpoClientOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExpoClientOptions")
+            .field("access_token", &self.access_token.as_ref().map(|_| "..."))
+            .field("base_url", &self.base_url)
+            .field("compact_to", &self.compact_to)
+            .field("gzip", &self.gzip)
+            .field("gzip_level", &self.gzip_level)
+            .field("receipt_store", &self.receipt_store.is_some())
+            .field("on_invalid_token", &self.on_invalid_token)
+            .field("on_store_error", &self.on_store_error)
+            .field("ticket_retry", &self.ticket_retry)
+            .field("on_retry_error", &self.on_retry_error)
+            .field("sent_keys", &self.sent_keys.is_some())
+            .finish()
     }
+}
 
-    pub fn new_with_base_url(access_token: Option<String>, base_url: &str) -> Self {
+impl Expo {
+    pub fn new(options: ExpoClientOptions) -> Self {
         Self {
-            access_token,
-            base_url: base_url.to_string(),
+            access_token: options.access_token,
+            base_url: options
+                .base_url
+                .unwrap_or_else(|| "https://exp.host".to_string()),
             client: reqwest::Client::builder()
                 .gzip(true)
                 .build()
                 .expect("Client::new()"),
+            compact_to: options.compact_to,
+            gzip: options.gzip,
+            gzip_level: options.gzip_level,
+            receipt_store: options.receipt_store,
+            on_invalid_token: options.on_invalid_token,
+            on_store_error: options.on_store_error,
+            on_retry_error: options.on_retry_error,
+            ticket_retry: options.ticket_retry,
+            sent_keys: options.sent_keys,
         }
     }
 
+    pub fn new_with_base_url(access_token: Option<String>, base_url: &str) -> Self {
+        Self::new(ExpoClientOptions {
+            access_token,
+            base_url: Some(base_url.to_string()),
+            ..Default::default()
+        })
+    }
+
     pub fn is_expo_push_token(token: &str) -> bool {
         ((token.starts_with("ExponentPushToken[") || token.starts_with("ExpoPushToken["))
             && token.ends_with(']'))
@@ -104,25 +186,78 @@ impl Expo {
     /// #     Ok(())
     /// # }
     /// ```
-    pub async fn send_push_notifications<R>(
+    ///
+    /// Borrowed messages (`&ExpoPushMessage`, `&[ExpoPushMessage]`, `&Vec<ExpoPushMessage>`)
+    /// are serialized in place without being copied.
+    pub async fn send_push_notifications<'a, R>(
         &self,
         request: R,
     ) -> Result<Vec<ExpoPushTicket>, CustomError>
     where
-        R: TryIntoSendPushNotificationsRequest,
+        R: TryIntoSendPushNotificationsRequest<'a>,
     {
         let request = request.try_into_send_push_notifications_request()?;
-        let chunks = self.chunk_push_notifications(request.messages());
         let mut tickets = Vec::new();
-        for chunk in chunks {
-            let response: SendPushNotificationSuccessfulResponse = self
-                .send_request(Method::POST, "/--/api/v2/push/send", chunk)
-                .await?;
-            tickets.extend(response.data);
+        for chunk in self.chunk_push_notifications(request.messages()) {
+            tickets.extend(self.send_chunk(chunk).await?);
         }
         Ok(tickets)
     }
 
+    /// Send push notifications chunk by chunk, yielding the tickets of each chunk as soon as
+    /// Expo responds.
+    ///
+    /// `messages` is consumed lazily, so the whole audience never needs to be in memory.
+    /// Wrap an iterator with [`futures::stream::iter`] to pass it here. A failed chunk is
+    /// reported in [`PushChunkResult::result`] and does not stop the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_send_push_notifications_stream() -> anyhow::Result<()> {
+    /// #     use expo_push_notification_client::{Expo, ExpoPushMessage};
+    /// #     use futures::StreamExt as _;
+    /// #     let mut server = mockito::Server::new_async().await;
+    /// #     let mock = server
+    /// #         .mock("POST", "/--/api/v2/push/send")
+    /// #         .with_status(200)
+    /// #         .with_header("content-type", "application/json; charset=utf-8")
+    /// #         .with_body(r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#)
+    /// #         .create();
+    /// #     let expo = Expo::new_with_base_url(None, &server.url());
+    /// let messages = futures::stream::iter(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+    ///     .map(|token| ExpoPushMessage::builder([token]).build().expect("valid token"));
+    /// let mut results = std::pin::pin!(expo.send_push_notifications_stream(messages));
+    /// while let Some(chunk) = results.next().await {
+    ///     let tickets = chunk.result?;
+    ///     // One ticket per recipient, not per message.
+    ///     let recipients = chunk.messages.iter().map(|m| m.to().len()).sum::<usize>();
+    ///     assert_eq!(tickets.len(), recipients);
+    /// }
+    /// #     mock.assert();
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn send_push_notifications_stream<'a, S>(
+        &'a self,
+        messages: S,
+    ) -> impl Stream<Item = PushChunkResult> + 'a
+    where
+        S: Stream<Item = ExpoPushMessage> + 'a,
+    {
+        messages
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .then(move |(index, messages)| async move {
+                let result = self.send_chunk(&messages).await;
+                PushChunkResult {
+                    index,
+                    messages,
+                    result,
+                }
+            })
+    }
+
     /// Get push notification receipts
     ///
     ///  <https://docs.expo.dev/push-notifications/sending-notifications/#push-receipts>
@@ -190,18 +325,77 @@ impl Expo {
         let response: GetPushNotificationReceiptsSuccessfulResponse = self
             .send_request(Method::POST, "/--/api/v2/push/getReceipts", request)
             .await?;
+        for (id, receipt) in &response.data {
+            if let ExpoPushReceipt::Error(error) = receipt {
+                self.report_invalid_token(error, None, Some(id));
+            }
+        }
         Ok(response.data)
     }
 
-    // private methods
+    /// Poll push notification receipts until every id has one or `options.give_up_after`
+    /// has passed.
+    ///
+    /// Only the ids still missing are queried again. A query that fails is recorded in
+    /// [`ReceiptPollReport::errors`] and its ids are queried again at the next interval, so
+    /// the receipts already collected are never lost. Ids without a receipt when polling
+    /// gives up are listed in [`ReceiptPollReport::missing`].
+    ///
+    /// <https://docs.expo.dev/push-notifications/sending-notifications/#push-receipts>
+    pub async fn poll_receipts<I>(
+        &self,
+        ids: I,
+        options: ReceiptPollOptions,
+    ) -> Result<ReceiptPollReport, CustomError>
+    where
+        I: IntoIterator,
+        <I as IntoIterator>::Item: TryInto<ExpoPushReceiptId>,
+        <<I as IntoIterator>::Item as TryInto<ExpoPushReceiptId>>::Error: Into<CustomError>,
+    {
+        let mut missing = ids
+            .into_iter()
+            .map(|id| id.try_into().map_err(|e| e.into()))
+            .collect::<Result<Vec<ExpoPushReceiptId>, CustomError>>()?;
+        let give_up_at = tokio::time::Instant::now() + options.give_up_after;
+        let mut report = ReceiptPollReport::default();
 
-    async fn gzip(src: &[u8]) -> std::io::Result<Vec<u8>> {
-        let mut encoder = GzipEncoder::new(vec![]);
-        encoder.write_all(src).await?;
-        encoder.shutdown().await?;
-        Ok(encoder.into_inner())
+        tokio::time::sleep(options.initial_delay.min(options.give_up_after)).await;
+        loop {
+            let mut receipts = HashMap::new();
+            for chunk in missing.chunks(RECEIPT_IDS_CHUNK_SIZE) {
+                match self.get_push_notification_receipts(chunk.to_vec()).await {
+                    Ok(chunk) => receipts.extend(chunk),
+                    Err(e) => report.errors.push(e),
+                }
+            }
+            missing.retain(|id| match receipts.remove(id) {
+                Some(ExpoPushReceipt::Ok) => {
+                    report.ok.push(id.clone());
+                    false
+                }
+                Some(ExpoPushReceipt::Error(error)) => {
+                    report.error.insert(id.clone(), error);
+                    false
+                }
+                Some(ExpoPushReceipt::Unknown(status)) => {
+                    report.unknown.insert(id.clone(), status);
+                    false
+                }
+                None => true,
+            });
+
+            let now = tokio::time::Instant::now();
+            if missing.is_empty() || now + options.interval > give_up_at {
+                break;
+            }
+            tokio::time::sleep(options.interval).await;
+        }
+        report.missing = missing;
+        Ok(report)
     }
 
+    // private methods
+
     async fn send_request<S, T>(
         &self,
         method: Method,
@@ -227,16 +421,10 @@ impl Expo {
             );
         }
 
-        let body =
-            serde_json::to_vec(&body).map_err(|e| CustomError::SerializeErr(e.to_string()))?;
-        let body = if body.len() > 1024 {
+        let (body, is_gzip) = gzip::to_json_body(&body, self.gzip, self.gzip_level)?;
+        if is_gzip {
             headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
-            Self::gzip(&body)
-                .await
-                .map_err(|e| CustomError::GzipErr(e.to_string()))
-        } else {
-            Ok(body)
-        }?;
+        }
 
         match client
             .request(method, format!("{base_url}{path}"))
@@ -263,19 +451,308 @@ impl Expo {
         }
     }
 
-    fn chunk_push_notifications(
+    async fn send_chunk(
         &self,
-        messages: Vec<ExpoPushMessage>,
-    ) -> Vec<SendPushNotificationsRequest> {
-        messages
-            .chunks(100)
-            .map(|chunk| SendPushNotificationsRequest::from(chunk.to_vec()))
-            .collect()
+        chunk: &[ExpoPushMessage],
+    ) -> Result<Vec<ExpoPushTicket>, CustomError> {
+        let Some(store) = &self.sent_keys else {
+            return self.send_chunk_with_retry(chunk).await;
+        };
+        let now = SystemTime::now();
+        let mut first_by_key = HashMap::new();
+        let sources = chunk
+            .iter()
+            .enumerate()
+            .map(|(index, message)| {
+                let Some(key) = message.idempotency_key() else {
+                    return Ok(KeySource::Sent);
+                };
+                if let Some(replayed) = store.get(key, now)? {
+                    return Ok(KeySource::Replayed(replayed));
+                }
+                // A key repeated within the chunk is sent once, with the first message.
+                Ok(match first_by_key.entry(key) {
+                    Entry::Occupied(first) => KeySource::SameAs(*first.get()),
+                    Entry::Vacant(first) => {
+                        first.insert(index);
+                        KeySource::Sent
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, CustomError>>()?;
+        let sent = if sources
+            .iter()
+            .all(|source| matches!(source, KeySource::Sent))
+        {
+            self.send_chunk_with_retry(chunk).await?
+        } else {
+            let messages = chunk
+                .iter()
+                .zip(&sources)
+                .filter(|(_, source)| matches!(source, KeySource::Sent))
+                .map(|(message, _)| message.clone())
+                .collect::<Vec<_>>();
+            if messages.is_empty() {
+                vec![]
+            } else {
+                self.send_chunk_with_retry(&messages).await?
+            }
+        };
+
+        let mut sent = sent.into_iter();
+        let mut per_message: Vec<Vec<ExpoPushTicket>> = Vec::with_capacity(chunk.len());
+        for (message, source) in chunk.iter().zip(sources) {
+            let own = match source {
+                KeySource::Replayed(replayed) => replayed,
+                KeySource::SameAs(first) => per_message[first].clone(),
+                KeySource::Sent => {
+                    let own = sent.by_ref().take(message.to().len()).collect::<Vec<_>>();
+                    // Replaying a retryable error would keep the message from ever being
+                    // sent again.
+                    let resendable = own.iter().any(|ticket| {
+                        matches!(ticket, ExpoPushTicket::Unknown(_)) || is_retryable(ticket)
+                    });
+                    if let Some(key) = message.idempotency_key().filter(|_| !resendable) {
+                        if let Err(err) = store.put(key, own.clone(), now) {
+                            self.report_store_error(err);
+                        }
+                    }
+                    own
+                }
+            };
+            per_message.push(own);
+        }
+        Ok(per_message.into_iter().flatten().collect())
+    }
+
+    async fn send_chunk_with_retry(
+        &self,
+        chunk: &[ExpoPushMessage],
+    ) -> Result<Vec<ExpoPushTicket>, CustomError> {
+        let mut tickets = self.send_chunk_once(chunk).await?;
+        let Some(retry) = &self.ticket_retry else {
+            return Ok(tickets);
+        };
+        let recipients = chunk
+            .iter()
+            .flat_map(|message| message.to().iter().map(move |to| (message, to)))
+            .collect::<Vec<_>>();
+        let mut backoff = retry.initial_backoff;
+        for _ in 1..retry.max_attempts {
+            let retryable = tickets
+                .iter()
+                .enumerate()
+                .filter(|(_, ticket)| is_retryable(ticket))
+                .filter_map(|(index, _)| recipients.get(index).map(|recipient| (index, recipient)))
+                .collect::<Vec<_>>();
+            if retryable.is_empty() {
+                break;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(retry.max_backoff);
+
+            let messages = retryable
+                .iter()
+                .map(|(_, (message, to))| message.with_recipient(to))
+                .collect::<Vec<_>>();
+            let mut resent = vec![];
+            for chunk in self.chunk_push_notifications(&messages) {
+                match self.send_chunk_once(chunk).await {
+                    Ok(tickets) => resent.extend(tickets),
+                    // The tickets already received are worth more than the error.
+                    Err(err) => {
+                        if let Some(sender) = &self.on_retry_error {
+                            // The receiver being dropped only means nobody listens anymore.
+                            let _ = sender.send(err);
+                        }
+                        break;
+                    }
+                }
+            }
+            for ((index, _), ticket) in retryable.into_iter().zip(resent) {
+                tickets[index] = ticket;
+            }
+        }
+        Ok(tickets)
+    }
+
+    async fn send_chunk_once(
+        &self,
+        chunk: &[ExpoPushMessage],
+    ) -> Result<Vec<ExpoPushTicket>, CustomError> {
+        let response: SendPushNotificationSuccessfulResponse = self
+            .send_request(
+                Method::POST,
+                "/--/api/v2/push/send",
+                SerializeMessages {
+                    messages: chunk,
+                    compact: self.compact_to,
+                },
+            )
+            .await?;
+        if let Some(store) = &self.receipt_store {
+            let sent_at = SystemTime::now();
+            let tokens = chunk.iter().flat_map(|message| message.to());
+            let pending = tokens
+                .zip(&response.data)
+                .filter_map(|(token, ticket)| match ticket {
+                    ExpoPushTicket::Ok(ticket) => Some(PendingReceipt {
+                        id: ticket.id.clone(),
+                        token: token.clone(),
+                        sent_at,
+                    }),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if !pending.is_empty() {
+                if let Err(err) = store.put(pending) {
+                    self.report_store_error(err);
+                }
+            }
+        }
+        let tokens = chunk.iter().flat_map(|message| message.to());
+        for (token, ticket) in tokens.zip(&response.data) {
+            if let ExpoPushTicket::Error(error) = ticket {
+                self.report_invalid_token(error, Some(token), None);
+            }
+        }
+        Ok(response.data)
+    }
+
+    fn report_invalid_token(
+        &self,
+        error: &ExpoPushErrorReceipt,
+        token: Option<&String>,
+        receipt_id: Option<&ExpoPushReceiptId>,
+    ) {
+        let Some(sender) = &self.on_invalid_token else {
+            return;
+        };
+        let Some(details) = error
+            .details
+            .as_ref()
+            .filter(|details| details.error == Some(DetailsErrorType::DeviceNotRegistered))
+        else {
+            return;
+        };
+        let token = details
+            .expo_push_token
+            .clone()
+            .or_else(|| token.cloned())
+            .or_else(|| {
+                // e.g. "\"ExponentPushToken[xxx]\" is not a registered push notification recipient"
+                error
+                    .message
+                    .split('"')
+                    .nth(1)
+                    .filter(|token| Self::is_expo_push_token(token))
+                    .map(str::to_string)
+            });
+        // The receiver being dropped only means nobody listens anymore.
+        let _ = sender.send(InvalidToken {
+            token,
+            receipt_id: receipt_id.cloned(),
+            message: error.message.clone(),
+            details: details.clone(),
+        });
+    }
+
+    fn report_store_error(&self, err: CustomError) {
+        if let Some(sender) = &self.on_store_error {
+            // The receiver being dropped only means nobody listens anymore.
+            let _ = sender.send(err);
+        }
+    }
+
+    fn chunk_push_notifications<'a>(
+        &self,
+        messages: &'a [ExpoPushMessage],
+    ) -> Vec<&'a [ExpoPushMessage]> {
+        messages.chunks(CHUNK_SIZE).collect()
+    }
+}
+
+// Where the tickets of a message come from when `sent_keys` is set.
+enum KeySource {
+    Sent,
+    Replayed(Vec<ExpoPushTicket>),
+    /// Index of the earlier message of the chunk with the same idempotency key.
+    SameAs(usize),
+}
+
+fn is_retryable(ticket: &ExpoPushTicket) -> bool {
+    match ticket {
+        ExpoPushTicket::Error(error) => error
+            .details
+            .as_ref()
+            .and_then(|details| details.error.as_ref())
+            .is_some_and(DetailsErrorType::is_retryable),
+        _ => false,
+    }
+}
+
+/// An Expo server that accepts a full chunk sent to `ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]`
+/// and fails the chunk sent to `ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]`, each exactly once.
+#[cfg(test)]
+pub(crate) struct SplitChunkServer {
+    server: mockito::ServerGuard,
+    accepted: mockito::Mock,
+    failed: mockito::Mock,
+}
+
+#[cfg(test)]
+impl SplitChunkServer {
+    pub(crate) const CHUNK_SIZE: usize = CHUNK_SIZE;
+
+    pub(crate) async fn new() -> Self {
+        let mut server = mockito::Server::new_async().await;
+        let accepted = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(mockito::Matcher::Regex("xxxx".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(format!(
+                r#"{{ "data": [{}] }}"#,
+                vec![
+                    r#"{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }"#;
+                    CHUNK_SIZE
+                ]
+                .join(",")
+            ))
+            .expect(1)
+            .create();
+        let failed = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(mockito::Matcher::Regex("yyyy".to_string()))
+            .with_status(500)
+            .expect(1)
+            .create();
+        Self {
+            server,
+            accepted,
+            failed,
+        }
+    }
+
+    /// A client for this server that never compresses, so the matchers can read the bodies.
+    pub(crate) fn expo(&self) -> Expo {
+        Expo::new(ExpoClientOptions {
+            base_url: Some(self.server.url()),
+            gzip: GzipPolicy::Never,
+            ..Default::default()
+        })
+    }
+
+    pub(crate) fn assert(&self) {
+        self.accepted.assert();
+        self.failed.assert();
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use async_compression::tokio::write::GzipEncoder;
+
     use crate::{
         Details, DetailsErrorType, ExpoPushErrorReceipt, ExpoPushMessage, ExpoPushSuccessTicket,
     };
@@ -322,7 +799,7 @@ mod tests {
                 })
                 .collect::<Result<Vec<_>, _>>()?;
 
-            let chunks = expo.chunk_push_notifications(messages);
+            let chunks = expo.chunk_push_notifications(&messages);
 
             assert_eq!(
                 chunks.len(),
@@ -382,6 +859,153 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_poll_receipts() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .match_body(r#"{"ids":["XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX","YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY","ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+{
+    "data": {
+        "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX": { "status": "ok" }
+    }
+}
+"#,
+            )
+            .create();
+        let second = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .match_body(r#"{"ids":["YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY","ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+{
+    "data": {
+        "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY": {
+            "status": "error",
+            "message": "\"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]\" is not a registered push notification recipient",
+            "details": { "error": "DeviceNotRegistered" }
+        }
+    }
+}
+"#,
+            )
+            .create();
+        let third = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .match_body(r#"{"ids":["ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(r#"{ "data": {} }"#)
+            .expect_at_least(1)
+            .create();
+
+        let expo = Expo::new_with_base_url(None, &server.url());
+        let report = expo
+            .poll_receipts(
+                [
+                    "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX",
+                    "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY",
+                    "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ",
+                ],
+                ReceiptPollOptions {
+                    initial_delay: std::time::Duration::ZERO,
+                    interval: std::time::Duration::from_millis(10),
+                    give_up_after: std::time::Duration::from_millis(100),
+                },
+            )
+            .await?;
+
+        assert_eq!(
+            report.ok,
+            vec![ExpoPushReceiptId::from_str(
+                "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"
+            )?]
+        );
+        assert_eq!(
+            report
+                .error
+                .get(&ExpoPushReceiptId::from_str(
+                    "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY"
+                )?)
+                .and_then(|e| e.details.as_ref())
+                .and_then(|d| d.error.clone()),
+            Some(DetailsErrorType::DeviceNotRegistered)
+        );
+        assert_eq!(
+            report.missing,
+            vec![ExpoPushReceiptId::from_str(
+                "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ"
+            )?]
+        );
+        first.assert();
+        second.assert();
+        third.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_poll_receipts_keeps_report_on_error() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .match_body(r#"{"ids":["XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX","YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(r#"{ "data": { "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX": { "status": "ok" } } }"#)
+            .create();
+        let failed = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .match_body(r#"{"ids":["YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY"]}"#)
+            .with_status(500)
+            .expect(1)
+            .create();
+        let third = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .match_body(r#"{"ids":["YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": { "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY": { "status": "ok" } } }"#,
+            )
+            .expect(1)
+            .create();
+
+        let expo = Expo::new_with_base_url(None, &server.url());
+        let report = expo
+            .poll_receipts(
+                [
+                    "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX",
+                    "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY",
+                ],
+                ReceiptPollOptions {
+                    initial_delay: std::time::Duration::ZERO,
+                    interval: std::time::Duration::from_millis(10),
+                    give_up_after: std::time::Duration::from_secs(1),
+                },
+            )
+            .await?;
+
+        assert_eq!(
+            report.ok,
+            vec![
+                ExpoPushReceiptId::from_str("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX")?,
+                ExpoPushReceiptId::from_str("YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY")?,
+            ]
+        );
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.missing.is_empty());
+        first.assert();
+        failed.assert();
+        third.assert();
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_get_push_notification_receipts_error_response() -> anyhow::Result<()> {
         let mut server = mockito::Server::new_async().await;
@@ -419,7 +1043,7 @@ mod tests {
                     ExpoPushReceiptId::from_str("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX")?,
                     ExpoPushReceipt::Error(ExpoPushErrorReceipt {
                         message: "\"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]\" is not a registered push notification recipient".to_string(),
-                        details: Some(Details { error: Some(DetailsErrorType::DeviceNotRegistered) }),
+                        details: Some(Details { error: Some(DetailsErrorType::DeviceNotRegistered), ..Default::default() }),
                     }),
                 );
             map
@@ -636,82 +1260,647 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_send_push_notifications_error_response() -> anyhow::Result<()> {
+    async fn test_send_push_notifications_compact_to() -> anyhow::Result<()> {
         let mut server = mockito::Server::new_async().await;
         let mock = server
                 .mock("POST", "/--/api/v2/push/send")
-                .match_header("accept-encoding", "gzip")
                 .match_header("content-type", "application/json")
-                .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#)
+                .match_body(r#"[{"to":"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"},{"to":["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]","ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"]}]"#)
                 .with_status(200)
                 .with_header("content-type", "application/json; charset=utf-8")
                 .with_body(
                     r#"
     {
         "data": [
-            {
-                "status": "error",
-                "message": "\"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]\" is not a registered push notification recipient",
-                "details": {
-                    "error": "DeviceNotRegistered"
-                }
-            }
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            { "status": "ok", "id": "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY" },
+            { "status": "ok", "id": "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ" }
         ]
     }
     "#,
                 )
                 .create();
 
-        let expo = Expo::new_with_base_url(None, &server.url());
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            compact_to: true,
+            ..Default::default()
+        });
 
         let response = expo
-            .send_push_notifications(
+            .send_push_notifications([
                 ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
-            )
+                ExpoPushMessage::builder([
+                    "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+                    "ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]",
+                ])
+                .build()?,
+            ])
             .await?;
-        assert_eq!(
-                response,
-                vec![ExpoPushTicket::Error(ExpoPushErrorReceipt {
-                    message: r#""ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]" is not a registered push notification recipient"#.to_string(),
-                    details: Some(Details {
-                        error: Some(DetailsErrorType::DeviceNotRegistered),
-                    })
-                })]
-            );
+
+        assert_eq!(response.len(), 3);
         mock.assert();
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_send_push_notifications_4xx() -> anyhow::Result<()> {
+    async fn test_send_push_notifications_stream() -> anyhow::Result<()> {
         let mut server = mockito::Server::new_async().await;
         let mock = server
             .mock("POST", "/--/api/v2/push/send")
-            .match_header("accept-encoding", "gzip")
-            .match_header("content-type", "application/json")
-            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#)
-            .with_status(401)
+            .with_status(200)
             .with_header("content-type", "application/json; charset=utf-8")
             .with_body(
                 r#"
     {
-        "error": "invalid_token",
-        "error_description":"The bearer token is invalid"
+        "data": [
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }
+        ]
     }
     "#,
             )
+            .expect(2)
             .create();
-
         let expo = Expo::new_with_base_url(None, &server.url());
 
-        let result = expo
-            .send_push_notifications(
-                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
-            )
+        let messages = futures::stream::iter(0..150).map(|_| {
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+                .build()
+                .expect("valid token")
+        });
+        let results = expo
+            .send_push_notifications_stream(messages)
+            .collect::<Vec<_>>()
             .await;
-        assert!(result.is_err());
+
         assert_eq!(
-            result.unwrap_err().to_string(),
+            results
+                .iter()
+                .map(|chunk| (chunk.index, chunk.messages.len(), chunk.result.is_ok()))
+                .collect::<Vec<_>>(),
+            vec![(0, 100, true), (1, 50, true)]
+        );
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_stream_error_chunk() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(500)
+            .expect(2)
+            .create();
+        let expo = Expo::new_with_base_url(None, &server.url());
+
+        let messages = futures::stream::iter(0..101).map(|_| {
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+                .build()
+                .expect("valid token")
+        });
+        let results = expo
+            .send_push_notifications_stream(messages)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(results.len(), 2);
+        for chunk in results {
+            assert_eq!(
+                chunk.result,
+                Err(CustomError::ServerErr(
+                    "Request failed: 500 Internal Server Error".to_string()
+                ))
+            );
+        }
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_records_receipts() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "data": [
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            { "status": "error", "message": "", "details": { "error": "DeviceNotRegistered" } },
+            { "status": "ok", "id": "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ" }
+        ]
+    }
+    "#,
+            )
+            .create();
+        let store = Arc::new(crate::MemoryReceiptStore::new());
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            receipt_store: Some(store.clone()),
+            ..Default::default()
+        });
+
+        expo.send_push_notifications([
+            ExpoPushMessage::builder([
+                "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+                "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+            ])
+            .build()?,
+            ExpoPushMessage::builder(["ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"]).build()?,
+        ])
+        .await?;
+
+        let pending = store.list_due(SystemTime::now(), std::time::Duration::ZERO)?;
+        assert_eq!(
+            pending
+                .iter()
+                .map(|receipt| (receipt.id.to_string(), receipt.token.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX".to_string(),
+                    "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"
+                ),
+                (
+                    "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ".to_string(),
+                    "ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"
+                ),
+            ]
+        );
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_store_error() -> anyhow::Result<()> {
+        struct FailingStore;
+
+        impl ReceiptStore for FailingStore {
+            fn put(&self, _: Vec<PendingReceipt>) -> Result<(), CustomError> {
+                Err(CustomError::StoreErr("disk full".to_string()))
+            }
+
+            fn list_due(
+                &self,
+                _: SystemTime,
+                _: std::time::Duration,
+            ) -> Result<Vec<PendingReceipt>, CustomError> {
+                Ok(vec![])
+            }
+
+            fn mark_resolved(&self, _: &[ExpoPushReceiptId]) -> Result<(), CustomError> {
+                Ok(())
+            }
+
+            fn expire(&self, _: SystemTime) -> Result<Vec<PendingReceipt>, CustomError> {
+                Ok(vec![])
+            }
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .create();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            receipt_store: Some(Arc::new(FailingStore)),
+            on_store_error: Some(sender),
+            ..Default::default()
+        });
+
+        let tickets = expo
+            .send_push_notifications(
+                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+            )
+            .await?;
+        assert_eq!(tickets.len(), 1);
+        assert!(matches!(tickets[0], ExpoPushTicket::Ok(_)));
+        assert_eq!(
+            receiver.try_recv()?,
+            CustomError::StoreErr("disk full".to_string())
+        );
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_ticket_retry() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(
+                r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]","ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"],"title":"Hello"}"#,
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "data": [
+            { "status": "error", "message": "slow down", "details": { "error": "MessageRateExceeded" } },
+            { "status": "error", "message": "unregistered", "details": { "error": "DeviceNotRegistered" } },
+            { "status": "error", "message": "oops", "details": { "error": "ExpoError" } }
+        ]
+    }
+    "#,
+            )
+            .expect(1)
+            .create();
+        let second = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(
+                r#"[{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],"title":"Hello"},{"to":["ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"],"title":"Hello"}]"#,
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "data": [
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            { "status": "error", "message": "oops", "details": { "error": "ExpoError" } }
+        ]
+    }
+    "#,
+            )
+            .expect(1)
+            .create();
+        let third = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"],"title":"Hello"}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ" }] }"#,
+            )
+            .expect(1)
+            .create();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            ticket_retry: Some(TicketRetryOptions {
+                initial_backoff: std::time::Duration::ZERO,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let tickets = expo
+            .send_push_notifications(
+                ExpoPushMessage::builder([
+                    "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+                    "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+                    "ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]",
+                ])
+                .title("Hello")
+                .build()?,
+            )
+            .await?;
+        assert!(matches!(
+            &tickets[..],
+            [
+                ExpoPushTicket::Ok(x),
+                ExpoPushTicket::Error(_),
+                ExpoPushTicket::Ok(z),
+            ] if x.id.to_string() == "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"
+                && z.id.to_string() == "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ"
+        ));
+        first.assert();
+        second.assert();
+        third.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_ticket_retry_error() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],"title":"Hello"}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "error", "message": "slow down", "details": { "error": "MessageRateExceeded" } }] }"#,
+            )
+            .expect(1)
+            .create();
+        let resend = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(500)
+            .expect(1)
+            .create();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            ticket_retry: Some(TicketRetryOptions {
+                max_attempts: 2,
+                initial_backoff: std::time::Duration::ZERO,
+                ..Default::default()
+            }),
+            on_retry_error: Some(sender),
+            ..Default::default()
+        });
+
+        let tickets = expo
+            .send_push_notifications(
+                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+                    .title("Hello")
+                    .build()?,
+            )
+            .await?;
+        // The original ticket is kept and the resend error is reported.
+        assert!(matches!(&tickets[..], [ExpoPushTicket::Error(e)] if e.message == "slow down"));
+        assert!(matches!(receiver.try_recv()?, CustomError::ServerErr(_)));
+        first.assert();
+        resend.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_idempotency_key() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],"title":"a"}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .expect(1)
+            .create();
+        let second = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"],"title":"b"}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY" }] }"#,
+            )
+            .expect(1)
+            .create();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            sent_keys: Some(Arc::new(crate::MemorySentKeyStore::new(
+                std::time::Duration::from_secs(3600),
+            ))),
+            ..Default::default()
+        });
+        let a = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .title("a")
+            .idempotency_key("a")
+            .build()?;
+        let b = ExpoPushMessage::builder(["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"])
+            .title("b")
+            .idempotency_key("b")
+            .build()?;
+
+        let original = expo.send_push_notifications(&a).await?;
+        let tickets = expo.send_push_notifications([a, b]).await?;
+        assert_eq!(tickets[0], original[0]);
+        assert!(matches!(
+            &tickets[1],
+            ExpoPushTicket::Ok(ticket) if ticket.id.to_string() == "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY"
+        ));
+        first.assert();
+        second.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_idempotency_key_repeated() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],"title":"a"}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .expect(1)
+            .create();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            sent_keys: Some(Arc::new(crate::MemorySentKeyStore::new(
+                std::time::Duration::from_secs(3600),
+            ))),
+            ..Default::default()
+        });
+        let message = |title| {
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+                .title(title)
+                .idempotency_key("a")
+                .build()
+        };
+
+        // The second message shares the key of the first and is not sent.
+        let tickets = expo
+            .send_push_notifications([message("a")?, message("b")?])
+            .await?;
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0], tickets[1]);
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_idempotency_key_retryable() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "error", "message": "", "details": { "error": "MessageRateExceeded" } }] }"#,
+            )
+            .expect(2)
+            .create();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            sent_keys: Some(Arc::new(crate::MemorySentKeyStore::new(
+                std::time::Duration::from_secs(3600),
+            ))),
+            ..Default::default()
+        });
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .idempotency_key("a")
+            .build()?;
+
+        // The rate-limited message is sent again instead of replaying its error.
+        expo.send_push_notifications(&message).await?;
+        expo.send_push_notifications(&message).await?;
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_on_invalid_token() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let send = server
+            .mock("POST", "/--/api/v2/push/send")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "data": [
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            { "status": "error", "message": "unregistered", "details": { "error": "DeviceNotRegistered" } },
+            { "status": "error", "message": "too big", "details": { "error": "MessageTooBig" } }
+        ]
+    }
+    "#,
+            )
+            .create();
+        let receipts = server
+            .mock("POST", "/--/api/v2/push/getReceipts")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "data": {
+            "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX": {
+                "status": "error",
+                "message": "\"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]\" is not a registered push notification recipient",
+                "details": { "error": "DeviceNotRegistered" }
+            }
+        }
+    }
+    "#,
+            )
+            .create();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            on_invalid_token: Some(sender),
+            ..Default::default()
+        });
+
+        expo.send_push_notifications(
+            ExpoPushMessage::builder([
+                "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+                "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+                "ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]",
+            ])
+            .build()?,
+        )
+        .await?;
+        expo.get_push_notification_receipts(["XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"])
+            .await?;
+        drop(expo);
+
+        let mut reported = vec![];
+        while let Some(invalid_token) = receiver.recv().await {
+            reported.push((invalid_token.token, invalid_token.receipt_id));
+        }
+        assert_eq!(
+            reported,
+            vec![
+                (
+                    Some("ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]".to_string()),
+                    None
+                ),
+                (
+                    Some("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]".to_string()),
+                    Some(ExpoPushReceiptId::from_str(
+                        "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"
+                    )?)
+                ),
+            ]
+        );
+        send.assert();
+        receipts.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_error_response() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+                .mock("POST", "/--/api/v2/push/send")
+                .match_header("accept-encoding", "gzip")
+                .match_header("content-type", "application/json")
+                .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#)
+                .with_status(200)
+                .with_header("content-type", "application/json; charset=utf-8")
+                .with_body(
+                    r#"
+    {
+        "data": [
+            {
+                "status": "error",
+                "message": "\"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]\" is not a registered push notification recipient",
+                "details": {
+                    "error": "DeviceNotRegistered"
+                }
+            }
+        ]
+    }
+    "#,
+                )
+                .create();
+
+        let expo = Expo::new_with_base_url(None, &server.url());
+
+        let response = expo
+            .send_push_notifications(
+                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+            )
+            .await?;
+        assert_eq!(
+                response,
+                vec![ExpoPushTicket::Error(ExpoPushErrorReceipt {
+                    message: r#""ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]" is not a registered push notification recipient"#.to_string(),
+                    details: Some(Details {
+                        error: Some(DetailsErrorType::DeviceNotRegistered),
+                        ..Default::default()
+                    })
+                })]
+            );
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_4xx() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_header("accept-encoding", "gzip")
+            .match_header("content-type", "application/json")
+            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#)
+            .with_status(401)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+    {
+        "error": "invalid_token",
+        "error_description":"The bearer token is invalid"
+    }
+    "#,
+            )
+            .create();
+
+        let expo = Expo::new_with_base_url(None, &server.url());
+
+        let result = expo
+            .send_push_notifications(
+                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
             "Server error: Request failed: 401 Unauthorized"
         );
         mock.assert();
@@ -783,6 +1972,7 @@ mod tests {
                             message: "\"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]\" is not a registered push notification recipient".to_string(),
                             details: Some(Details {
                                 error: Some(DetailsErrorType::DeviceNotRegistered),
+                                ..Default::default()
                             })
                         }),
                         ExpoPushTicket::Ok(ExpoPushSuccessTicket {
@@ -885,6 +2075,64 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_send_push_notifications_gzip_never() -> anyhow::Result<()> {
+        let to = ["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"].repeat(24);
+        let request = serde_json::to_vec(&serde_json::json!({ "to": to }))?;
+        assert!(request.len() > 1024);
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_header("content-encoding", mockito::Matcher::Missing)
+            .match_body(request)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .create();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            gzip: GzipPolicy::Never,
+            ..Default::default()
+        });
+
+        expo.send_push_notifications(ExpoPushMessage::builder(to).build()?)
+            .await?;
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_push_notifications_gzip_always() -> anyhow::Result<()> {
+        let request = br#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#;
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_header("content-encoding", "gzip")
+            .match_body(gzip(request).await?)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .create();
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            gzip: GzipPolicy::Always,
+            ..Default::default()
+        });
+
+        expo.send_push_notifications(
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+        )
+        .await?;
+        mock.assert();
+        Ok(())
+    }
+
     async fn gzip(src: &[u8]) -> std::io::Result<Vec<u8>> {
         let mut encoder = GzipEncoder::new(vec![]);
         tokio::io::AsyncWriteExt::write_all(&mut encoder, src).await?;
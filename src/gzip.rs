@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::error::CustomError;
+
+/// When request bodies are gzip-compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GzipPolicy {
+    Never,
+    Always,
+    /// Compress bodies whose JSON is longer than the given number of bytes.
+    Above(usize),
+}
+
+impl Default for GzipPolicy {
+    fn default() -> Self {
+        GzipPolicy::Above(1024)
+    }
+}
+
+/// Serializes `value` as JSON, compressing it on the fly once it exceeds the threshold of
+/// `policy`. Returns the body and whether it is gzip-compressed.
+///
+/// `level` ranges from 0 (no compression) to 9 (best compression). `None` uses the default.
+pub(crate) fn to_json_body<S>(
+    value: &S,
+    policy: GzipPolicy,
+    level: Option<u32>,
+) -> Result<(Vec<u8>, bool), CustomError>
+where
+    S: serde::Serialize,
+{
+    let level = level.map_or_else(Compression::default, |level| Compression::new(level.min(9)));
+    let mut writer = match policy {
+        GzipPolicy::Never => BodyWriter::Plain {
+            buf: vec![],
+            threshold: None,
+            level,
+        },
+        GzipPolicy::Always => BodyWriter::Gzip(GzEncoder::new(vec![], level)),
+        GzipPolicy::Above(threshold) => BodyWriter::Plain {
+            buf: vec![],
+            threshold: Some(threshold),
+            level,
+        },
+    };
+    serde_json::to_writer(&mut writer, value).map_err(|e| {
+        if e.is_io() {
+            CustomError::GzipErr(e.to_string())
+        } else {
+            CustomError::SerializeErr(e.to_string())
+        }
+    })?;
+    match writer {
+        BodyWriter::Plain { buf, .. } => Ok((buf, false)),
+        BodyWriter::Gzip(encoder) => encoder
+            .finish()
+            .map(|body| (body, true))
+            .map_err(|e| CustomError::GzipErr(e.to_string())),
+    }
+}
+
+// Buffers plain JSON until the threshold is exceeded, then moves the buffered bytes into a
+// gzip encoder and streams the rest of the JSON through it.
+enum BodyWriter {
+    Plain {
+        buf: Vec<u8>,
+        threshold: Option<usize>,
+        level: Compression,
+    },
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        match self {
+            BodyWriter::Plain {
+                buf,
+                threshold,
+                level,
+            } => {
+                if matches!(threshold, Some(threshold) if buf.len() + data.len() > *threshold) {
+                    let mut encoder = GzEncoder::new(vec![], *level);
+                    encoder.write_all(buf)?;
+                    encoder.write_all(data)?;
+                    *self = BodyWriter::Gzip(encoder);
+                } else {
+                    buf.extend_from_slice(data);
+                }
+                Ok(data.len())
+            }
+            BodyWriter::Gzip(encoder) => encoder.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            BodyWriter::Plain { .. } => Ok(()),
+            BodyWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use super::*;
+
+    fn gunzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut decoded = vec![];
+        flate2::read::GzDecoder::new(body).read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_to_json_body_policies() -> anyhow::Result<()> {
+        let ids = ["XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"].repeat(27);
+        let value = serde_json::json!({ "ids": ids });
+        let json = serde_json::to_vec(&value)?;
+        assert_eq!(json.len(), 1062);
+
+        for (policy, compressed) in [
+            (GzipPolicy::Never, false),
+            (GzipPolicy::Always, true),
+            (GzipPolicy::default(), true),
+            (GzipPolicy::Above(1062), false),
+            (GzipPolicy::Above(1061), true),
+        ] {
+            let (body, is_gzip) = to_json_body(&value, policy, None)?;
+            assert_eq!(is_gzip, compressed, "{policy:?}");
+            if compressed {
+                assert_eq!(gunzip(&body)?, json, "{policy:?}");
+            } else {
+                assert_eq!(body, json, "{policy:?}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_json_body_levels() -> anyhow::Result<()> {
+        let ids = ["XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"].repeat(100);
+        let value = serde_json::json!({ "ids": ids });
+        let json = serde_json::to_vec(&value)?;
+
+        let (stored, _) = to_json_body(&value, GzipPolicy::Always, Some(0))?;
+        let (best, _) = to_json_body(&value, GzipPolicy::Always, Some(9))?;
+        assert!(best.len() < stored.len());
+        assert_eq!(gunzip(&stored)?, json);
+        assert_eq!(gunzip(&best)?, json);
+        Ok(())
+    }
+}
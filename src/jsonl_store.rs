@@ -0,0 +1,205 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead as _, BufReader, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::CustomError;
+
+/// The append-only JSON Lines file behind the file-backed stores.
+///
+/// Entries are appended as they happen and replayed on [`JsonlFile::open`]. A store compacts
+/// the file by rewriting it with the entries still needed.
+#[derive(Debug)]
+pub(crate) struct JsonlFile {
+    path: PathBuf,
+    file: Mutex<Appender>,
+}
+
+/// The open file, locked by [`JsonlFile::lock`] for as long as a store updates its state.
+#[derive(Debug)]
+pub(crate) struct Appender {
+    file: File,
+    lines: usize,
+}
+
+impl JsonlFile {
+    /// Opens the file at `path`, creating it if needed, and passes every entry already in it
+    /// to `replay`, oldest first.
+    pub(crate) fn open<T, F>(path: &Path, mut replay: F) -> Result<Self, CustomError>
+    where
+        T: DeserializeOwned,
+        F: FnMut(T) -> Result<(), CustomError>,
+    {
+        let mut lines = 0;
+        if path.exists() {
+            let file = File::open(path).map_err(store_err)?;
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(store_err)?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                replay(serde_json::from_str(&line).map_err(store_err)?)?;
+                lines += 1;
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(Appender {
+                file: open_append(path)?,
+                lines,
+            }),
+        })
+    }
+
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'_, Appender>, CustomError> {
+        self.file.lock().map_err(store_err)
+    }
+
+    /// Replaces the content of the file with `entries`, through a temporary file renamed
+    /// over it so that a crash leaves either the old or the new content.
+    pub(crate) fn compact<T>(
+        &self,
+        appender: &mut Appender,
+        entries: &[T],
+    ) -> Result<(), CustomError>
+    where
+        T: Serialize,
+    {
+        let tmp = self.tmp_path();
+        let mut compacted = File::create(&tmp).map_err(store_err)?;
+        write_entries(&mut compacted, entries)?;
+        std::fs::rename(&tmp, &self.path).map_err(store_err)?;
+        *appender = Appender {
+            file: open_append(&self.path)?,
+            lines: entries.len(),
+        };
+        Ok(())
+    }
+
+    // `data.outbox` and `data.receipts` must not share a temporary file, so the suffix is
+    // appended to the whole file name.
+    fn tmp_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        self.path.with_file_name(name)
+    }
+}
+
+impl Appender {
+    pub(crate) fn append<T>(&mut self, entries: &[T]) -> Result<(), CustomError>
+    where
+        T: Serialize,
+    {
+        write_entries(&mut self.file, entries)?;
+        self.lines += entries.len();
+        Ok(())
+    }
+
+    /// Number of entries in the file, to decide when to compact it.
+    pub(crate) fn lines(&self) -> usize {
+        self.lines
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, CustomError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(store_err)
+}
+
+fn write_entries<T>(file: &mut File, entries: &[T]) -> Result<(), CustomError>
+where
+    T: Serialize,
+{
+    let mut buf = vec![];
+    for entry in entries {
+        serde_json::to_writer(&mut buf, entry).map_err(store_err)?;
+        buf.push(b'\n');
+    }
+    file.write_all(&buf)
+        .and_then(|_| file.flush())
+        .map_err(store_err)
+}
+
+fn store_err<E>(e: E) -> CustomError
+where
+    E: std::fmt::Display,
+{
+    CustomError::StoreErr(e.to_string())
+}
+
+/// A file in the temporary directory, unique per process and thread, removed on drop.
+#[cfg(test)]
+pub(crate) struct TempPath(PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "expo_push_{}_{:?}_{name}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_file() -> anyhow::Result<()> {
+        let path = TempPath::new("entries.jsonl");
+        let file = JsonlFile::open(path.as_ref(), |_: u64| Ok(()))?;
+        let mut appender = file.lock()?;
+        appender.append(&[1u64, 2, 3])?;
+        file.compact(&mut appender, &[3u64])?;
+        appender.append(&[4u64])?;
+        assert_eq!(appender.lines(), 2);
+        drop(appender);
+
+        let mut replayed = vec![];
+        let file = JsonlFile::open(path.as_ref(), |entry: u64| {
+            replayed.push(entry);
+            Ok(())
+        })?;
+        assert_eq!(replayed, [3, 4]);
+        assert_eq!(file.lock()?.lines(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tmp_path_is_unique_per_file() -> anyhow::Result<()> {
+        let outbox_path = TempPath::new("data.outbox");
+        let receipts_path = TempPath::new("data.receipts");
+        let outbox = JsonlFile::open(outbox_path.as_ref(), |_: u64| Ok(()))?;
+        let receipts = JsonlFile::open(receipts_path.as_ref(), |_: u64| Ok(()))?;
+        assert_ne!(outbox.tmp_path(), receipts.tmp_path());
+
+        outbox.compact(&mut *outbox.lock()?, &[1u64])?;
+        receipts.compact(&mut *receipts.lock()?, &[2u64])?;
+        assert_eq!(std::fs::read_to_string(&outbox_path)?, "1\n");
+        assert_eq!(std::fs::read_to_string(&receipts_path)?, "2\n");
+        Ok(())
+    }
+}
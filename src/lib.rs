@@ -1,11 +1,41 @@
+mod bulk;
+mod clock;
 mod error;
 mod expo_client;
+mod gzip;
+mod jsonl_store;
+mod localization;
 mod object;
+mod outbox;
+mod receipt_store;
+#[cfg(feature = "scheduler")]
+mod scheduler;
+mod sent_key_store;
+mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-pub use error::{CustomError, ValidationError};
+#[cfg(feature = "csv")]
+pub use bulk::{read_csv, CsvRecipient};
+pub use bulk::{read_jsonl, ResultWriter, SendResult};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use error::{CustomError, LineError, ValidationError};
 pub use expo_client::{Expo, ExpoClientOptions};
+pub use gzip::GzipPolicy;
+pub use localization::LocalizedMessage;
 pub use object::{
-    Details, DetailsErrorType, ExpoPushErrorReceipt, ExpoPushMessage, ExpoPushMessageBuilder,
-    ExpoPushReceipt, ExpoPushReceiptId, ExpoPushSuccessTicket, ExpoPushTicket, InterruptionLevel,
-    Priority, RichContent, Sound,
+    AndroidOptions, DeduplicationReport, DeliveryReport, Details, DetailsErrorType, ErrorBreakdown,
+    ExpoPushErrorReceipt, ExpoPushMessage, ExpoPushMessageBuilder, ExpoPushReceipt,
+    ExpoPushReceiptId, ExpoPushSuccessTicket, ExpoPushTicket, InterruptionLevel, InvalidToken,
+    IosOptions, Priority, PushChunkResult, ReceiptPollOptions, ReceiptPollReport, RemovedRecipient,
+    RichContent, SendPushNotificationsRequest, Sound, TicketRetryOptions,
+    TryIntoSendPushNotificationsRequest, UNSPECIFIED_ERROR,
 };
+pub use outbox::{FileOutbox, MemoryOutbox, Outbox, OutboxEntry, OutboxSent, OutboxWorker};
+pub use receipt_store::{
+    FileReceiptStore, MemoryReceiptStore, PendingReceipt, ReceiptStore, RECEIPT_RETENTION,
+};
+#[cfg(feature = "scheduler")]
+pub use scheduler::{QuietHours, Scheduler};
+pub use sent_key_store::{FileSentKeyStore, MemorySentKeyStore, SentKeyStore};
+pub use template::MessageTemplate;
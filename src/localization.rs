@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use crate::{ExpoPushMessage, ExpoPushMessageBuilder, ValidationError};
+
+/// A message with a title and body per locale, sent as one [`ExpoPushMessage`] per locale.
+///
+/// A recipient's locale falls back to less specific ones and then to the default locale,
+/// e.g. `pt-BR` → `pt` → `en`. Locales are matched case-insensitively, with `_` read as
+/// `-`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_localized_message() -> anyhow::Result<()> {
+/// use expo_push_notification_client::{ExpoPushMessage, LocalizedMessage};
+///
+/// let messages = LocalizedMessage::new(ExpoPushMessage::builder(Vec::<String>::new()), "en")
+///     .variant("en", "Hello", "You have a new message")
+///     .variant("pt", "Olá", "Você tem uma nova mensagem")
+///     .build_messages([
+///         ("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]", "pt-BR"),
+///         ("ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]", "fr"),
+///     ])?;
+/// assert_eq!(messages[0].title(), Some("Olá"));
+/// assert_eq!(messages[1].title(), Some("Hello"));
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocalizedMessage {
+    builder: ExpoPushMessageBuilder,
+    default_locale: String,
+    variants: HashMap<String, (String, String)>,
+}
+
+impl LocalizedMessage {
+    /// `builder` holds the fields shared by every locale; its recipients are replaced.
+    pub fn new<S>(builder: ExpoPushMessageBuilder, default_locale: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            builder,
+            default_locale: normalize(default_locale.as_ref()),
+            variants: HashMap::new(),
+        }
+    }
+
+    pub fn variant<L, T, B>(mut self, locale: L, title: T, body: B) -> Self
+    where
+        L: AsRef<str>,
+        T: Into<String>,
+        B: Into<String>,
+    {
+        self.variants
+            .insert(normalize(locale.as_ref()), (title.into(), body.into()));
+        self
+    }
+
+    /// Groups `recipients` (token, locale) by the locale they resolve to, in order of first
+    /// appearance. Fails with [`ValidationError::MissingTranslation`] when neither a
+    /// recipient's locale nor the default locale has a variant.
+    pub fn build_messages<T, L, I>(
+        &self,
+        recipients: I,
+    ) -> Result<Vec<ExpoPushMessage>, ValidationError>
+    where
+        I: IntoIterator<Item = (T, L)>,
+        T: Into<String>,
+        L: AsRef<str>,
+    {
+        let mut groups: Vec<(&str, Vec<String>)> = vec![];
+        for (token, locale) in recipients {
+            let locale = self.resolve(locale.as_ref())?;
+            match groups.iter_mut().find(|(l, _)| *l == locale) {
+                Some((_, tokens)) => tokens.push(token.into()),
+                None => groups.push((locale, vec![token.into()])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(locale, tokens)| {
+                let (title, body) = &self.variants[locale];
+                self.builder
+                    .clone()
+                    .to(tokens)
+                    .title(title)
+                    .body(body)
+                    .build()
+            })
+            .collect()
+    }
+
+    fn resolve(&self, locale: &str) -> Result<&str, ValidationError> {
+        fallback_chain(&normalize(locale), &self.default_locale)
+            .into_iter()
+            .find_map(|locale| self.variants.get_key_value(&locale))
+            .map(|(locale, _)| locale.as_str())
+            .ok_or_else(|| ValidationError::MissingTranslation(locale.to_string()))
+    }
+}
+
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+// `zh-hant-tw` → [`zh-hant-tw`, `zh-hant`, `zh`, default]
+fn fallback_chain(locale: &str, default_locale: &str) -> Vec<String> {
+    let mut chain = vec![];
+    let mut locale = locale;
+    while !locale.is_empty() {
+        chain.push(locale.to_string());
+        locale = locale.rsplit_once('-').map_or("", |(parent, _)| parent);
+    }
+    chain.push(default_locale.to_string());
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_chain() {
+        assert_eq!(
+            fallback_chain("zh-hant-tw", "en"),
+            ["zh-hant-tw", "zh-hant", "zh", "en"]
+        );
+        assert_eq!(fallback_chain("", "en"), ["en"]);
+    }
+
+    #[test]
+    fn test_build_messages() -> anyhow::Result<()> {
+        let localized = LocalizedMessage::new(
+            ExpoPushMessage::builder(Vec::<String>::new()).badge(1),
+            "en",
+        )
+        .variant("en", "Hello", "Hi")
+        .variant("pt", "Olá", "Oi")
+        .variant("pt-BR", "Olá!", "E aí");
+        let messages = localized.build_messages([
+            ("ExponentPushToken[aaaaaaaaaaaaaaaaaaaaaa]", "pt_br"),
+            ("ExponentPushToken[bbbbbbbbbbbbbbbbbbbbbb]", "de-DE"),
+            ("ExponentPushToken[cccccccccccccccccccccc]", "pt-PT"),
+            ("ExponentPushToken[dddddddddddddddddddddd]", "PT-BR"),
+        ])?;
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| (message.title(), message.to().len(), message.badge()))
+                .collect::<Vec<_>>(),
+            [
+                (Some("Olá!"), 2, Some(1)),
+                (Some("Hello"), 1, Some(1)),
+                (Some("Olá"), 1, Some(1)),
+            ]
+        );
+        assert_eq!(
+            messages[0].to(),
+            [
+                "ExponentPushToken[aaaaaaaaaaaaaaaaaaaaaa]",
+                "ExponentPushToken[dddddddddddddddddddddd]"
+            ]
+        );
+
+        let without_default =
+            LocalizedMessage::new(ExpoPushMessage::builder(Vec::<String>::new()), "en")
+                .variant("pt", "Olá", "Oi");
+        assert_eq!(
+            without_default.build_messages([("ExponentPushToken[aaaaaaaaaaaaaaaaaaaaaa]", "fr")]),
+            Err(ValidationError::MissingTranslation("fr".to_string()))
+        );
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+mod android_options;
+mod delivery_report;
 mod details;
 mod expo_push_error_receipt;
 mod expo_push_message;
@@ -6,11 +8,18 @@ mod expo_push_receipt_id;
 mod expo_push_success_ticket;
 mod expo_push_ticket;
 mod interruption_level;
+mod invalid_token;
+mod ios_options;
 mod priority;
+mod push_chunk_result;
+mod receipt_poll;
 mod rich_content;
 mod send_push_notifications_request;
 mod sound;
+mod ticket_retry;
 
+pub use self::android_options::*;
+pub use self::delivery_report::*;
 pub use self::details::*;
 pub use self::expo_push_error_receipt::*;
 pub use self::expo_push_message::*;
@@ -19,7 +28,12 @@ pub use self::expo_push_receipt_id::*;
 pub use self::expo_push_success_ticket::*;
 pub use self::expo_push_ticket::*;
 pub use self::interruption_level::*;
+pub use self::invalid_token::*;
+pub use self::ios_options::*;
 pub use self::priority::*;
+pub use self::push_chunk_result::*;
+pub use self::receipt_poll::*;
 pub use self::rich_content::*;
 pub use self::send_push_notifications_request::*;
 pub use self::sound::*;
+pub use self::ticket_retry::*;
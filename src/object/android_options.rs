@@ -0,0 +1,34 @@
+/// Android-only fields of an [`ExpoPushMessage`](crate::ExpoPushMessage).
+///
+/// These are all the Android fields of the Expo message format. It has no FCM `tag` or
+/// `collapse_key`, so notifications cannot be grouped or collapsed through Expo.
+///
+/// <https://docs.expo.dev/push-notifications/sending-notifications/#message-request-format>
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AndroidOptions {
+    pub channel_id: Option<String>,
+    pub icon: Option<String>,
+}
+
+impl AndroidOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn channel_id<S>(mut self, channel_id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.channel_id = Some(channel_id.into());
+        self
+    }
+
+    /// Name of an Android drawable resource, e.g. `myicon`.
+    pub fn icon<S>(mut self, icon: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.icon = Some(icon.into());
+        self
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DetailsErrorType, ExpoPushErrorReceipt, ExpoPushMessage, ExpoPushReceipt, ExpoPushReceiptId,
+    ExpoPushTicket,
+};
+
+/// Key of [`DeliveryReport::errors`] for errors without `details.error`.
+pub const UNSPECIFIED_ERROR: &str = "Unspecified";
+
+/// Outcome of a campaign, built from its tickets and the receipts fetched for them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryReport {
+    /// Number of tickets, one per recipient.
+    pub total: usize,
+    /// Tickets with status `ok`.
+    pub accepted: usize,
+    /// Receipts with status `ok`.
+    pub delivered: usize,
+    /// Ticket and receipt errors.
+    pub failed: usize,
+    /// Tickets and receipts with a status not known to this crate.
+    pub unknown: usize,
+    /// Failures by `details.error`, or [`UNSPECIFIED_ERROR`].
+    pub errors: BTreeMap<String, ErrorBreakdown>,
+    /// Accepted tickets with no receipt yet.
+    pub missing_receipts: Vec<ExpoPushReceiptId>,
+    /// Tokens whose error is worth retrying, see [`DetailsErrorType::is_retryable`].
+    pub retry_candidates: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorBreakdown {
+    pub count: usize,
+    pub tokens: Vec<String>,
+}
+
+impl DeliveryReport {
+    /// `tickets` must be in the order returned for `messages`, one per recipient.
+    pub fn new(
+        messages: &[ExpoPushMessage],
+        tickets: &[ExpoPushTicket],
+        receipts: &HashMap<ExpoPushReceiptId, ExpoPushReceipt>,
+    ) -> Self {
+        let mut report = Self {
+            total: tickets.len(),
+            ..Default::default()
+        };
+        let mut tokens = messages.iter().flat_map(|message| message.to());
+        for ticket in tickets {
+            let token = tokens.next();
+            match ticket {
+                ExpoPushTicket::Ok(ticket) => {
+                    report.accepted += 1;
+                    match receipts.get(&ticket.id) {
+                        Some(ExpoPushReceipt::Ok) => report.delivered += 1,
+                        Some(ExpoPushReceipt::Error(error)) => report.add_error(error, token),
+                        Some(ExpoPushReceipt::Unknown(_)) => report.unknown += 1,
+                        None => report.missing_receipts.push(ticket.id.clone()),
+                    }
+                }
+                ExpoPushTicket::Error(error) => report.add_error(error, token),
+                ExpoPushTicket::Unknown(_) => report.unknown += 1,
+            }
+        }
+        report
+    }
+
+    fn add_error(&mut self, error: &ExpoPushErrorReceipt, token: Option<&String>) {
+        self.failed += 1;
+        let error_type = error
+            .details
+            .as_ref()
+            .and_then(|details| details.error.as_ref());
+        let token = error
+            .details
+            .as_ref()
+            .and_then(|details| details.expo_push_token.as_ref())
+            .or(token);
+        let breakdown = self
+            .errors
+            .entry(
+                error_type
+                    .map_or(UNSPECIFIED_ERROR, DetailsErrorType::as_str)
+                    .to_string(),
+            )
+            .or_default();
+        breakdown.count += 1;
+        if let Some(token) = token {
+            breakdown.tokens.push(token.clone());
+            if error_type.is_some_and(DetailsErrorType::is_retryable) {
+                self.retry_candidates.push(token.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use super::*;
+
+    #[test]
+    fn test_delivery_report() -> anyhow::Result<()> {
+        let message = ExpoPushMessage::builder([
+            "ExponentPushToken[aaaaaaaaaaaaaaaaaaaaaa]",
+            "ExponentPushToken[bbbbbbbbbbbbbbbbbbbbbb]",
+            "ExponentPushToken[cccccccccccccccccccccc]",
+            "ExponentPushToken[dddddddddddddddddddddd]",
+            "ExponentPushToken[eeeeeeeeeeeeeeeeeeeeee]",
+        ])
+        .build()?;
+        let tickets = serde_json::from_value::<Vec<ExpoPushTicket>>(serde_json::json!([
+            { "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" },
+            { "status": "ok", "id": "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY" },
+            { "status": "ok", "id": "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ" },
+            { "status": "error", "message": "slow down", "details": { "error": "MessageRateExceeded" } },
+            { "status": "error", "message": "oops" }
+        ]))?;
+        let receipts = serde_json::from_value::<HashMap<ExpoPushReceiptId, ExpoPushReceipt>>(
+            serde_json::json!({
+                "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX": { "status": "ok" },
+                "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY": {
+                    "status": "error",
+                    "message": "unregistered",
+                    "details": { "error": "DeviceNotRegistered" }
+                }
+            }),
+        )?;
+
+        let report = DeliveryReport::new(&[message], &tickets, &receipts);
+        assert_eq!(
+            report,
+            DeliveryReport {
+                total: 5,
+                accepted: 3,
+                delivered: 1,
+                failed: 3,
+                unknown: 0,
+                errors: BTreeMap::from([
+                    (
+                        "DeviceNotRegistered".to_string(),
+                        ErrorBreakdown {
+                            count: 1,
+                            tokens: vec!["ExponentPushToken[bbbbbbbbbbbbbbbbbbbbbb]".to_string()],
+                        }
+                    ),
+                    (
+                        "MessageRateExceeded".to_string(),
+                        ErrorBreakdown {
+                            count: 1,
+                            tokens: vec!["ExponentPushToken[dddddddddddddddddddddd]".to_string()],
+                        }
+                    ),
+                    (
+                        UNSPECIFIED_ERROR.to_string(),
+                        ErrorBreakdown {
+                            count: 1,
+                            tokens: vec!["ExponentPushToken[eeeeeeeeeeeeeeeeeeeeee]".to_string()],
+                        }
+                    ),
+                ]),
+                missing_receipts: vec![ExpoPushReceiptId::from_str(
+                    "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ"
+                )?],
+                retry_candidates: vec!["ExponentPushToken[dddddddddddddddddddddd]".to_string()],
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&report)?["errors"]["MessageRateExceeded"],
+            serde_json::json!({
+                "count": 1,
+                "tokens": ["ExponentPushToken[dddddddddddddddddddddd]"]
+            })
+        );
+        Ok(())
+    }
+}
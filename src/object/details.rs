@@ -1,11 +1,23 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use serde_with::skip_serializing_none;
 
-#[derive(Debug, Clone, Eq, Deserialize, PartialEq, Serialize)]
+/// Details of a ticket or receipt error.
+///
+/// Fields not modelled here, such as provider-specific info, are kept in `extra`.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Eq, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Details {
     pub error: Option<DetailsErrorType>,
+    /// The token the error refers to, e.g. the one to remove on `DeviceNotRegistered`.
+    pub expo_push_token: Option<String>,
+    pub fault: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Eq, Deserialize, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum DetailsErrorType {
     DeveloperError,
     DeviceNotRegistered,
@@ -14,4 +26,141 @@ pub enum DetailsErrorType {
     MessageTooBig,
     MessageRateExceeded,
     ProviderError,
+    /// An error code not known to this crate, kept as is.
+    Unknown(String),
+}
+
+impl DetailsErrorType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DetailsErrorType::DeveloperError => "DeveloperError",
+            DetailsErrorType::DeviceNotRegistered => "DeviceNotRegistered",
+            DetailsErrorType::ExpoError => "ExpoError",
+            DetailsErrorType::InvalidCredentials => "InvalidCredentials",
+            DetailsErrorType::MessageTooBig => "MessageTooBig",
+            DetailsErrorType::MessageRateExceeded => "MessageRateExceeded",
+            DetailsErrorType::ProviderError => "ProviderError",
+            DetailsErrorType::Unknown(s) => s,
+        }
+    }
+
+    /// Whether sending again later may succeed: `MessageRateExceeded` and `ExpoError`.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            DetailsErrorType::MessageRateExceeded | DetailsErrorType::ExpoError
+        )
+    }
+}
+
+impl From<String> for DetailsErrorType {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "DeveloperError" => DetailsErrorType::DeveloperError,
+            "DeviceNotRegistered" => DetailsErrorType::DeviceNotRegistered,
+            "ExpoError" => DetailsErrorType::ExpoError,
+            "InvalidCredentials" => DetailsErrorType::InvalidCredentials,
+            "MessageTooBig" => DetailsErrorType::MessageTooBig,
+            "MessageRateExceeded" => DetailsErrorType::MessageRateExceeded,
+            "ProviderError" => DetailsErrorType::ProviderError,
+            _ => DetailsErrorType::Unknown(s),
+        }
+    }
+}
+
+impl Serialize for DetailsErrorType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DetailsErrorType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(DetailsErrorType::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_error_type() -> Result<(), serde_json::Error> {
+        assert_eq!(
+            serde_json::from_str::<Details>(r#"{"error":"DeviceNotRegistered"}"#)?,
+            Details {
+                error: Some(DetailsErrorType::DeviceNotRegistered),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Details>(r#"{"error":"InvalidProviderToken"}"#)?,
+            Details {
+                error: Some(DetailsErrorType::Unknown(
+                    "InvalidProviderToken".to_string()
+                )),
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_all_fields() -> Result<(), serde_json::Error> {
+        let details = serde_json::from_str::<Details>(
+            r#"
+{
+    "error": "DeviceNotRegistered",
+    "expoPushToken": "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+    "fault": "developer",
+    "apns": { "reason": "Unregistered", "statusCode": 410 }
+}
+"#,
+        )?;
+        assert_eq!(
+            details,
+            Details {
+                error: Some(DetailsErrorType::DeviceNotRegistered),
+                expo_push_token: Some("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]".to_string()),
+                fault: Some("developer".to_string()),
+                extra: serde_json::json!({
+                    "apns": { "reason": "Unregistered", "statusCode": 410 }
+                })
+                .as_object()
+                .cloned()
+                .unwrap_or_default(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&details)?,
+            serde_json::json!({
+                "error": "DeviceNotRegistered",
+                "expoPushToken": "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+                "fault": "developer",
+                "apns": { "reason": "Unregistered", "statusCode": 410 }
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_error_type() -> Result<(), serde_json::Error> {
+        assert_eq!(
+            serde_json::to_string(&DetailsErrorType::MessageRateExceeded)?,
+            "\"MessageRateExceeded\""
+        );
+        assert_eq!(
+            serde_json::to_string(&DetailsErrorType::Unknown(
+                "InvalidProviderToken".to_string()
+            ))?,
+            "\"InvalidProviderToken\""
+        );
+        Ok(())
+    }
 }
@@ -1,18 +1,123 @@
-use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use serde_json::Value;
-use serde_with::skip_serializing_none;
+use serde_with::{formats::PreferMany, serde_as, skip_serializing_none, OneOrMany};
 
 use crate::error::ValidationError;
+use crate::object::android_options::AndroidOptions;
 use crate::object::interruption_level::InterruptionLevel;
+use crate::object::ios_options::IosOptions;
 use crate::object::priority::Priority;
 use crate::object::rich_content::RichContent;
 use crate::object::sound::Sound;
 
+// FCM rejects a ttl longer than 4 weeks.
+const MAX_TTL_SECS: u64 = 2_419_200;
+// Unix timestamps in seconds between 2001-09-09 and 2286-11-20. Anything outside
+// this range is almost certainly a relative value or a timestamp in milliseconds.
+const MIN_EXPIRATION_SECS: u64 = 1_000_000_000;
+const MAX_EXPIRATION_SECS: u64 = 10_000_000_000;
+
 // <https://docs.expo.dev/push-notifications/sending-notifications/#message-request-format>
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpoPushMessage {
+    to: Vec<String>,
+    title: Option<String>,
+    body: Option<String>,
+    data: Option<Value>,
+    ttl: Option<u64>,
+    expiration: Option<u64>,
+    priority: Option<Priority>,
+    subtitle: Option<String>,
+    sound: Option<Sound>,
+    badge: Option<u64>,
+    channel_id: Option<String>,
+    icon: Option<String>,
+    category_id: Option<String>,
+    mutable_content: Option<bool>,
+    rich_content: Option<RichContent>,
+    _content_available: Option<bool>,
+    interruption_level: Option<InterruptionLevel>,
+    idempotency_key: Option<String>,
+}
+
+// Wire shape written when serializing. `to` is written as a string only in the compact form.
 #[skip_serializing_none]
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ExpoPushMessage {
+struct ExpoPushMessageRef<'a> {
+    to: Recipients<'a>,
+    title: Option<&'a str>,
+    body: Option<&'a str>,
+    data: Option<&'a Value>,
+    ttl: Option<u64>,
+    expiration: Option<u64>,
+    priority: Option<&'a Priority>,
+    subtitle: Option<&'a str>,
+    sound: Option<&'a Sound>,
+    badge: Option<u64>,
+    channel_id: Option<&'a str>,
+    icon: Option<&'a str>,
+    category_id: Option<&'a str>,
+    mutable_content: Option<bool>,
+    rich_content: Option<&'a RichContent>,
+    #[serde(rename = "_contentAvailable")]
+    _content_available: Option<bool>,
+    interruption_level: Option<&'a InterruptionLevel>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Recipients<'a> {
+    One(&'a str),
+    Many(&'a [String]),
+}
+
+impl<'a> ExpoPushMessageRef<'a> {
+    fn new(message: &'a ExpoPushMessage, compact: bool) -> Self {
+        Self {
+            to: match message.to.as_slice() {
+                [to] if compact => Recipients::One(to),
+                to => Recipients::Many(to),
+            },
+            title: message.title(),
+            body: message.body(),
+            data: message.data(),
+            ttl: message.ttl,
+            expiration: message.expiration,
+            priority: message.priority(),
+            subtitle: message.subtitle(),
+            sound: message.sound(),
+            badge: message.badge,
+            channel_id: message.channel_id(),
+            icon: message.icon(),
+            category_id: message.category_id(),
+            mutable_content: message.mutable_content,
+            rich_content: message.rich_content(),
+            _content_available: message._content_available,
+            interruption_level: message.interruption_level(),
+        }
+    }
+}
+
+impl Serialize for ExpoPushMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        ExpoPushMessageRef::new(self, false).serialize(serializer)
+    }
+}
+
+// Wire shape accepted when deserializing. It goes through `ExpoPushMessageBuilder::build`
+// so that deserialized messages are validated like built ones, but never against the
+// current time.
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExpoPushMessageWire {
+    #[serde_as(as = "OneOrMany<_, PreferMany>")]
     to: Vec<String>,
     title: Option<String>,
     body: Option<String>,
@@ -24,6 +129,7 @@ pub struct ExpoPushMessage {
     sound: Option<Sound>,
     badge: Option<u64>,
     channel_id: Option<String>,
+    icon: Option<String>,
     category_id: Option<String>,
     mutable_content: Option<bool>,
     rich_content: Option<RichContent>,
@@ -32,6 +138,37 @@ pub struct ExpoPushMessage {
     interruption_level: Option<InterruptionLevel>,
 }
 
+impl<'de> Deserialize<'de> for ExpoPushMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let wire = ExpoPushMessageWire::deserialize(deserializer)?;
+        ExpoPushMessageBuilder {
+            to: wire.to,
+            title: wire.title,
+            body: wire.body,
+            data: wire.data,
+            ttl: wire.ttl,
+            expiration: wire.expiration,
+            priority: wire.priority,
+            subtitle: wire.subtitle,
+            sound: wire.sound,
+            badge: wire.badge,
+            channel_id: wire.channel_id,
+            icon: wire.icon,
+            category_id: wire.category_id,
+            mutable_content: wire.mutable_content,
+            rich_content: wire.rich_content,
+            _content_available: wire._content_available,
+            interruption_level: wire.interruption_level,
+            idempotency_key: None,
+        }
+        .build()
+        .map_err(D::Error::custom)
+    }
+}
+
 impl ExpoPushMessage {
     pub fn builder<S, I>(to: I) -> ExpoPushMessageBuilder
     where
@@ -40,9 +177,170 @@ impl ExpoPushMessage {
     {
         ExpoPushMessageBuilder::new(to.into_iter().map(|s| s.into()).collect::<Vec<String>>())
     }
+
+    /// Returns a view of this message that serializes `to` as a string when there is a
+    /// single recipient.
+    pub fn compact(&self) -> impl Serialize + '_ {
+        ExpoPushMessageRef::new(self, true)
+    }
+
+    /// Splits this message into one message per recipient, keeping every other field.
+    ///
+    /// Useful to customize a message per token or to correlate each ticket with exactly
+    /// one message.
+    ///
+    /// An idempotency key is suffixed with `/` and the token so that every part keeps its
+    /// own.
+    pub fn split_by_recipient(&self) -> Vec<ExpoPushMessage> {
+        self.to
+            .iter()
+            .map(|to| ExpoPushMessage {
+                idempotency_key: self
+                    .idempotency_key
+                    .as_ref()
+                    .map(|key| format!("{key}/{to}")),
+                ..self.with_recipient(to)
+            })
+            .collect()
+    }
+
+    pub(crate) fn with_recipient(&self, to: &str) -> ExpoPushMessage {
+        self.with_recipients(vec![to.to_string()])
+    }
+
+    pub(crate) fn with_recipients(&self, to: Vec<String>) -> ExpoPushMessage {
+        ExpoPushMessage { to, ..self.clone() }
+    }
+
+    pub(crate) fn with_idempotency_key(self, idempotency_key: Option<String>) -> ExpoPushMessage {
+        ExpoPushMessage {
+            idempotency_key,
+            ..self
+        }
+    }
+
+    /// Returns a builder initialized with a copy of this message.
+    pub fn to_builder(&self) -> ExpoPushMessageBuilder {
+        self.clone().into_builder()
+    }
+
+    /// Turns this message back into a builder, e.g. to tweak a template per recipient.
+    /// The result is validated again by [`ExpoPushMessageBuilder::build`].
+    pub fn into_builder(self) -> ExpoPushMessageBuilder {
+        ExpoPushMessageBuilder {
+            to: self.to,
+            title: self.title,
+            body: self.body,
+            data: self.data,
+            ttl: self.ttl,
+            expiration: self.expiration,
+            priority: self.priority,
+            subtitle: self.subtitle,
+            sound: self.sound,
+            badge: self.badge,
+            channel_id: self.channel_id,
+            icon: self.icon,
+            category_id: self.category_id,
+            mutable_content: self.mutable_content,
+            rich_content: self.rich_content,
+            _content_available: self._content_available,
+            interruption_level: self.interruption_level,
+            idempotency_key: self.idempotency_key,
+        }
+    }
+
+    pub fn to(&self) -> &[String] {
+        &self.to
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    pub fn data(&self) -> Option<&Value> {
+        self.data.as_ref()
+    }
+
+    pub fn ttl(&self) -> Option<u64> {
+        self.ttl
+    }
+
+    pub fn expiration(&self) -> Option<u64> {
+        self.expiration
+    }
+
+    /// Checks the message against the given point in time.
+    ///
+    /// Expo gives `expiration` precedence over `ttl`, so an expiration that has already
+    /// passed at `now` would silently discard the ttl. [`ExpoPushMessageBuilder::build`]
+    /// does not look at the clock, so call this right before sending if that matters.
+    pub fn validate_at(&self, now: SystemTime) -> Result<(), ValidationError> {
+        if let (Some(_), Some(expiration)) = (self.ttl, self.expiration) {
+            let now = now
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| ValidationError::InvalidExpiration)?
+                .as_secs();
+            if expiration <= now {
+                return Err(ValidationError::InvalidExpiration);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn priority(&self) -> Option<&Priority> {
+        self.priority.as_ref()
+    }
+
+    pub fn subtitle(&self) -> Option<&str> {
+        self.subtitle.as_deref()
+    }
+
+    pub fn sound(&self) -> Option<&Sound> {
+        self.sound.as_ref()
+    }
+
+    pub fn badge(&self) -> Option<u64> {
+        self.badge
+    }
+
+    pub fn channel_id(&self) -> Option<&str> {
+        self.channel_id.as_deref()
+    }
+
+    pub fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    pub fn category_id(&self) -> Option<&str> {
+        self.category_id.as_deref()
+    }
+
+    pub fn mutable_content(&self) -> Option<bool> {
+        self.mutable_content
+    }
+
+    pub fn rich_content(&self) -> Option<&RichContent> {
+        self.rich_content.as_ref()
+    }
+
+    pub fn content_available(&self) -> Option<bool> {
+        self._content_available
+    }
+
+    pub fn interruption_level(&self) -> Option<&InterruptionLevel> {
+        self.interruption_level.as_ref()
+    }
+
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExpoPushMessageBuilder {
     to: Vec<String>,
     title: Option<String>,
@@ -55,11 +353,13 @@ pub struct ExpoPushMessageBuilder {
     sound: Option<Sound>,
     badge: Option<u64>,
     channel_id: Option<String>,
+    icon: Option<String>,
     category_id: Option<String>,
     mutable_content: Option<bool>,
     rich_content: Option<RichContent>,
     _content_available: Option<bool>,
     interruption_level: Option<InterruptionLevel>,
+    idempotency_key: Option<String>,
 }
 
 impl ExpoPushMessageBuilder {
@@ -76,6 +376,8 @@ impl ExpoPushMessageBuilder {
             sound: None,
             badge: None,
             channel_id: None,
+            icon: None,
+            idempotency_key: None,
             category_id: None,
             mutable_content: None,
             rich_content: None,
@@ -84,6 +386,16 @@ impl ExpoPushMessageBuilder {
         }
     }
 
+    /// Replaces the recipients.
+    pub fn to<S, I>(mut self, to: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.to = to.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
     pub fn body<S>(mut self, body: S) -> Self
     where
         S: Into<String>,
@@ -110,11 +422,32 @@ impl ExpoPushMessageBuilder {
         self
     }
 
+    /// Sets the ttl from a [`Duration`]. Sub-second precision is truncated.
+    pub fn ttl_duration(self, ttl: Duration) -> Self {
+        self.ttl(ttl.as_secs())
+    }
+
+    /// Sets the expiration as a Unix timestamp in seconds.
     pub fn expiration(mut self, expiration: u64) -> Self {
         self.expiration = Some(expiration);
         self
     }
 
+    /// Sets the expiration from an absolute point in time.
+    ///
+    /// Accepts anything convertible into [`SystemTime`], such as `chrono::DateTime<Utc>`
+    /// or `time::OffsetDateTime`.
+    pub fn expiration_at<T>(self, expiration: T) -> Result<Self, ValidationError>
+    where
+        T: Into<SystemTime>,
+    {
+        let expiration = expiration
+            .into()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| ValidationError::InvalidExpiration)?;
+        Ok(self.expiration(expiration.as_secs()))
+    }
+
     pub fn priority(mut self, priority: Priority) -> Self {
         self.priority = Some(priority);
         self
@@ -146,6 +479,15 @@ impl ExpoPushMessageBuilder {
         self
     }
 
+    /// Android only. Name of a drawable resource, e.g. `myicon`.
+    pub fn icon<S>(mut self, icon: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.icon = Some(icon.into());
+        self
+    }
+
     pub fn category_id<S>(mut self, category_id: S) -> Self
     where
         S: Into<String>,
@@ -174,10 +516,55 @@ impl ExpoPushMessageBuilder {
         self
     }
 
+    /// Applies the Android-only fields set in `android`, overriding previous values.
+    pub fn android(mut self, android: AndroidOptions) -> Self {
+        let AndroidOptions { channel_id, icon } = android;
+        self.channel_id = channel_id.or(self.channel_id);
+        self.icon = icon.or(self.icon);
+        self
+    }
+
+    /// Applies the iOS-only fields set in `ios`, overriding previous values.
+    pub fn ios(mut self, ios: IosOptions) -> Self {
+        let IosOptions {
+            subtitle,
+            sound,
+            badge,
+            mutable_content,
+            content_available,
+            interruption_level,
+        } = ios;
+        self.subtitle = subtitle.or(self.subtitle);
+        self.sound = sound.or(self.sound);
+        self.badge = badge.or(self.badge);
+        self.mutable_content = mutable_content.or(self.mutable_content);
+        self._content_available = content_available.or(self._content_available);
+        self.interruption_level = interruption_level.or(self.interruption_level);
+        self
+    }
+
+    /// Identifies the logical send of this message. When the client has a
+    /// [`SentKeyStore`](crate::SentKeyStore), a message whose key was already sent is
+    /// skipped and its original tickets are returned.
+    ///
+    /// The key is never sent to Expo and is not serialized with the message. An
+    /// [`OutboxEntry`](crate::OutboxEntry) stores it next to the message.
+    pub fn idempotency_key<S>(mut self, idempotency_key: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.idempotency_key = Some(idempotency_key.into());
+        self
+    }
+
     pub fn build(self) -> Result<ExpoPushMessage, ValidationError> {
         if !self.is_valid_expo_push_token() {
             return Err(ValidationError::InvalidToken);
         }
+        self.validate_ttl_and_expiration()?;
+        if let Some(rich_content) = &self.rich_content {
+            rich_content.validate()?;
+        }
 
         let message = ExpoPushMessage {
             to: self.to,
@@ -191,11 +578,13 @@ impl ExpoPushMessageBuilder {
             sound: self.sound,
             badge: self.badge,
             channel_id: self.channel_id,
+            icon: self.icon,
             category_id: self.category_id,
             mutable_content: self.mutable_content,
             rich_content: self.rich_content,
             _content_available: self._content_available,
             interruption_level: self.interruption_level,
+            idempotency_key: self.idempotency_key,
         };
 
         Ok(message)
@@ -209,6 +598,18 @@ impl ExpoPushMessageBuilder {
         self
     }
 
+    fn validate_ttl_and_expiration(&self) -> Result<(), ValidationError> {
+        if matches!(self.ttl, Some(ttl) if ttl > MAX_TTL_SECS) {
+            return Err(ValidationError::InvalidTtl);
+        }
+        if let Some(expiration) = self.expiration {
+            if !(MIN_EXPIRATION_SECS..MAX_EXPIRATION_SECS).contains(&expiration) {
+                return Err(ValidationError::InvalidExpiration);
+            }
+        }
+        Ok(())
+    }
+
     fn is_valid_expo_push_token(&self) -> bool {
         self.to.iter().all(|token| {
             ((token.starts_with("ExponentPushToken[") || token.starts_with("ExpoPushToken["))
@@ -242,7 +643,7 @@ mod tests {
             data: "data".to_string(),
         })?
         .ttl(100)
-        .expiration(100)
+        .expiration(4_102_444_800)
         .priority(Priority::High)
         .subtitle("subtitle")
         .sound(Sound::Default)
@@ -266,12 +667,14 @@ mod tests {
                 body: Some("body".to_string()),
                 data: Some(json!({ "data": "data" })),
                 ttl: Some(100),
-                expiration: Some(100),
+                expiration: Some(4_102_444_800),
                 priority: Some(Priority::High),
                 subtitle: Some("subtitle".to_string()),
                 sound: Some(Sound::Default),
                 badge: Some(1),
                 channel_id: Some("channel_id".to_string()),
+                icon: None,
+                idempotency_key: None,
                 category_id: Some("category_id".to_string()),
                 mutable_content: Some(true),
                 rich_content: None,
@@ -292,7 +695,7 @@ mod tests {
                 "data": "data"
             },
             "ttl": 100,
-            "expiration": 100,
+            "expiration": 4_102_444_800u64,
             "priority": "high",
             "subtitle": "subtitle",
             "sound": "default",
@@ -333,6 +736,8 @@ mod tests {
                 sound: None,
                 badge: None,
                 channel_id: None,
+                icon: None,
+                idempotency_key: None,
                 category_id: None,
                 mutable_content: None,
                 rich_content: Some(RichContent::new().image("https://example.com/image.png")),
@@ -378,6 +783,8 @@ mod tests {
                 sound: None,
                 badge: None,
                 channel_id: None,
+                icon: None,
+                idempotency_key: None,
                 category_id: None,
                 mutable_content: None,
                 rich_content: Some(RichContent::new()),
@@ -432,6 +839,8 @@ mod tests {
                 sound: None,
                 badge: None,
                 channel_id: None,
+                icon: None,
+                idempotency_key: None,
                 category_id: None,
                 mutable_content: None,
                 rich_content: None,
@@ -516,4 +925,285 @@ mod tests {
         assert_eq!(serialized["sound"], "bells.wav");
         Ok(())
     }
+
+    #[test]
+    fn test_expo_push_message_builder_with_typed_ttl_and_expiration() -> Result<(), ValidationError>
+    {
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .ttl_duration(Duration::from_millis(3_600_500))
+            .expiration_at(UNIX_EPOCH + Duration::from_secs(4_102_444_800))?
+            .build()?;
+
+        let serialized =
+            serde_json::to_value(&message).map_err(|_| ValidationError::InvalidData)?;
+        assert_eq!(serialized["ttl"], 3_600);
+        assert_eq!(serialized["expiration"], 4_102_444_800u64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_builder_invalid_ttl_and_expiration() {
+        let builder = || ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]);
+
+        // ttl given in milliseconds
+        assert_eq!(
+            builder().ttl(3_600_000).build(),
+            Err(ValidationError::InvalidTtl)
+        );
+        // expiration given in milliseconds
+        assert_eq!(
+            builder().expiration(4_102_444_800_000).build(),
+            Err(ValidationError::InvalidExpiration)
+        );
+        // expiration given as a relative value
+        assert_eq!(
+            builder().expiration(3_600).build(),
+            Err(ValidationError::InvalidExpiration)
+        );
+        // expiration already passed while a ttl is set
+        let message = builder().ttl(3_600).expiration(1_500_000_000).build();
+        assert!(message.is_ok());
+        assert_eq!(
+            message.unwrap().validate_at(SystemTime::now()),
+            Err(ValidationError::InvalidExpiration)
+        );
+        assert_eq!(
+            builder()
+                .expiration_at(UNIX_EPOCH - Duration::from_secs(1))
+                .map(|_| ()),
+            Err(ValidationError::InvalidExpiration)
+        );
+    }
+
+    #[test]
+    fn test_expo_push_message_builder_with_platform_options() -> Result<(), ValidationError> {
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .title("title")
+            .body("body")
+            .priority(Priority::High)
+            .category_id("category_id")
+            .android(
+                AndroidOptions::new()
+                    .channel_id("channel_id")
+                    .icon("myicon"),
+            )
+            .ios(
+                IosOptions::new()
+                    .subtitle("subtitle")
+                    .sound(Sound::Custom("bells.wav".to_string()))
+                    .badge(3)
+                    .mutable_content(true)
+                    .content_available(true)
+                    .interruption_level(InterruptionLevel::Passive),
+            )
+            .build()?;
+
+        let serialized =
+            serde_json::to_value(&message).map_err(|_| ValidationError::InvalidData)?;
+        let expected_json = json!({
+            "to": ["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],
+            "title": "title",
+            "body": "body",
+            "priority": "high",
+            "subtitle": "subtitle",
+            "sound": "bells.wav",
+            "badge": 3,
+            "channelId": "channel_id",
+            "icon": "myicon",
+            "categoryId": "category_id",
+            "mutableContent": true,
+            "_contentAvailable": true,
+            "interruptionLevel": "passive"
+        });
+        assert_eq!(serialized, expected_json);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_builder_platform_options_keep_unset_fields(
+    ) -> Result<(), ValidationError> {
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .channel_id("channel_id")
+            .badge(1)
+            .android(AndroidOptions::new().icon("myicon"))
+            .ios(IosOptions::new().sound(Sound::Default))
+            .build()?;
+
+        let serialized =
+            serde_json::to_value(&message).map_err(|_| ValidationError::InvalidData)?;
+        let expected_json = json!({
+            "to": ["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],
+            "sound": "default",
+            "badge": 1,
+            "channelId": "channel_id",
+            "icon": "myicon"
+        });
+        assert_eq!(serialized, expected_json);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_builder_invalid_rich_content_url() {
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .rich_content(RichContent::new().image("image.png"))
+            .build();
+
+        assert_eq!(message, Err(ValidationError::InvalidUrl));
+    }
+
+    #[test]
+    fn test_expo_push_message_getters() -> Result<(), ValidationError> {
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .title("title")
+            .body("body")
+            .data(&json!({ "id": 1 }))?
+            .priority(Priority::High)
+            .badge(1)
+            .build()?;
+
+        assert_eq!(message.to(), ["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]);
+        assert_eq!(message.title(), Some("title"));
+        assert_eq!(message.body(), Some("body"));
+        assert_eq!(message.data(), Some(&json!({ "id": 1 })));
+        assert_eq!(message.priority(), Some(&Priority::High));
+        assert_eq!(message.badge(), Some(1));
+        assert_eq!(message.subtitle(), None);
+        assert_eq!(message.content_available(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_builder_round_trip() -> Result<(), ValidationError> {
+        let template = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .title("title")
+            .body("body")
+            .build()?;
+
+        assert_eq!(template.to_builder().build()?, template);
+
+        let message = template
+            .to_builder()
+            .to(["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"])
+            .body("other body")
+            .build()?;
+        assert_eq!(message.to(), ["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"]);
+        assert_eq!(message.title(), Some("title"));
+        assert_eq!(message.body(), Some("other body"));
+        assert_eq!(template.body(), Some("body"));
+
+        assert_eq!(
+            template.into_builder().to(["invalid_token"]).build(),
+            Err(ValidationError::InvalidToken)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_deserialize() -> anyhow::Result<()> {
+        let message = serde_json::from_str::<ExpoPushMessage>(
+            r#"{"to":"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","title":"title","_contentAvailable":true}"#,
+        )?;
+        assert_eq!(
+            message,
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+                .title("title")
+                .content_available(true)
+                .build()?
+        );
+
+        let message = serde_json::from_str::<ExpoPushMessage>(
+            r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","ExpoPushToken[yyyyyyyyyyyyyyyyyyyyyy]"],"channelId":"channel_id"}"#,
+        )?;
+        assert_eq!(
+            message.to(),
+            [
+                "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+                "ExpoPushToken[yyyyyyyyyyyyyyyyyyyyyy]"
+            ]
+        );
+        assert_eq!(message.channel_id(), Some("channel_id"));
+
+        assert_eq!(
+            serde_json::from_value::<ExpoPushMessage>(serde_json::to_value(&message)?)?,
+            message
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_deserialize_expired() -> anyhow::Result<()> {
+        // A queued message must stay readable after its expiration has passed.
+        let message: ExpoPushMessage = serde_json::from_str(
+            r#"{"to":"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","ttl":3600,"expiration":1500000000}"#,
+        )?;
+        assert_eq!(message.expiration(), Some(1_500_000_000));
+        assert!(message.validate_at(SystemTime::now()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_deserialize_invalid() {
+        for json in [
+            r#"{"to":"invalid_token"}"#,
+            r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","invalid_token"]}"#,
+            r#"{"to":"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","richContent":{"image":"image.png"}}"#,
+            r#"{"title":"title"}"#,
+            r#"{"to":"ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]","expiration":1500000000000}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<ExpoPushMessage>(json).is_err(),
+                "{json} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_expo_push_message_compact() -> anyhow::Result<()> {
+        let message = ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+            .title("title")
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(message.compact())?,
+            json!({ "to": "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]", "title": "title" })
+        );
+
+        let message = ExpoPushMessage::builder([
+            "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+            "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+        ])
+        .build()?;
+        assert_eq!(
+            serde_json::to_value(message.compact())?,
+            json!({
+                "to": [
+                    "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+                    "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"
+                ]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expo_push_message_split_by_recipient() -> Result<(), ValidationError> {
+        let message = ExpoPushMessage::builder([
+            "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+            "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+        ])
+        .title("title")
+        .build()?;
+
+        assert_eq!(
+            message.split_by_recipient(),
+            vec![
+                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"])
+                    .title("title")
+                    .build()?,
+                ExpoPushMessage::builder(["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"])
+                    .title("title")
+                    .build()?,
+            ]
+        );
+        Ok(())
+    }
 }
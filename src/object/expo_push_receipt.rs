@@ -1,10 +1,50 @@
-use serde::Deserialize;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::Value;
 
 use super::expo_push_error_receipt::ExpoPushErrorReceipt;
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-#[serde(rename_all = "camelCase", tag = "status")]
+#[derive(Debug, PartialEq, Clone)]
 pub enum ExpoPushReceipt {
     Ok,
     Error(ExpoPushErrorReceipt),
+    /// A `status` not known to this crate, kept as is.
+    Unknown(String),
+}
+
+impl<'de> Deserialize<'de> for ExpoPushReceipt {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let status = value
+            .get("status")
+            .and_then(Value::as_str)
+            .ok_or_else(|| D::Error::missing_field("status"))?;
+        match status {
+            "ok" => Ok(ExpoPushReceipt::Ok),
+            "error" => ExpoPushErrorReceipt::deserialize(value)
+                .map(ExpoPushReceipt::Error)
+                .map_err(D::Error::custom),
+            _ => Ok(ExpoPushReceipt::Unknown(status.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_unknown_status() -> Result<(), serde_json::Error> {
+        assert_eq!(
+            serde_json::from_str::<ExpoPushReceipt>(r#"{"status":"ok"}"#)?,
+            ExpoPushReceipt::Ok
+        );
+        assert_eq!(
+            serde_json::from_str::<ExpoPushReceipt>(r#"{"status":"delayed"}"#)?,
+            ExpoPushReceipt::Unknown("delayed".to_string())
+        );
+        Ok(())
+    }
 }
@@ -1,10 +1,98 @@
+use serde::{
+    de::Error as _, ser::SerializeStruct as _, Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::Value;
+
 use crate::ExpoPushErrorReceipt;
 
 use super::expo_push_success_ticket::ExpoPushSuccessTicket;
 
-#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
-#[serde(rename_all = "camelCase", tag = "status")]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ExpoPushTicket {
     Ok(ExpoPushSuccessTicket),
     Error(ExpoPushErrorReceipt),
+    /// A `status` not known to this crate, kept as is.
+    Unknown(String),
+}
+
+impl<'de> Deserialize<'de> for ExpoPushTicket {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let status = value
+            .get("status")
+            .and_then(Value::as_str)
+            .ok_or_else(|| D::Error::missing_field("status"))?;
+        match status {
+            "ok" => ExpoPushSuccessTicket::deserialize(value)
+                .map(ExpoPushTicket::Ok)
+                .map_err(D::Error::custom),
+            "error" => ExpoPushErrorReceipt::deserialize(value)
+                .map(ExpoPushTicket::Error)
+                .map_err(D::Error::custom),
+            _ => Ok(ExpoPushTicket::Unknown(status.to_string())),
+        }
+    }
+}
+
+// The shape Expo sends, so that a serialized ticket deserializes back to itself.
+impl Serialize for ExpoPushTicket {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ExpoPushTicket::Ok(ticket) => {
+                let mut state = serializer.serialize_struct("ExpoPushTicket", 2)?;
+                state.serialize_field("status", "ok")?;
+                state.serialize_field("id", &ticket.id)?;
+                state.end()
+            }
+            ExpoPushTicket::Error(error) => {
+                let mut state = serializer.serialize_struct("ExpoPushTicket", 3)?;
+                state.serialize_field("status", "error")?;
+                state.serialize_field("message", &error.message)?;
+                state.serialize_field("details", &error.details)?;
+                state.end()
+            }
+            ExpoPushTicket::Unknown(status) => {
+                let mut state = serializer.serialize_struct("ExpoPushTicket", 1)?;
+                state.serialize_field("status", status)?;
+                state.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_round_trip() -> Result<(), serde_json::Error> {
+        for json in [
+            r#"{"status":"ok","id":"XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"}"#,
+            r#"{"status":"error","message":"m","details":{"error":"DeviceNotRegistered"}}"#,
+            r#"{"status":"pending"}"#,
+        ] {
+            let ticket = serde_json::from_str::<ExpoPushTicket>(json)?;
+            assert_eq!(
+                serde_json::from_str::<ExpoPushTicket>(&serde_json::to_string(&ticket)?)?,
+                ticket
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_unknown_status() -> Result<(), serde_json::Error> {
+        assert_eq!(
+            serde_json::from_str::<ExpoPushTicket>(r#"{"status":"pending","id":"x"}"#)?,
+            ExpoPushTicket::Unknown("pending".to_string())
+        );
+        assert!(serde_json::from_str::<ExpoPushTicket>(r#"{"id":"x"}"#).is_err());
+        Ok(())
+    }
 }
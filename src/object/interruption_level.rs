@@ -1,12 +1,55 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InterruptionLevel {
     Active,
     Critical,
     Passive,
     TimeSensitive,
+    /// A value not known to this crate, kept as is.
+    Unknown(String),
+}
+
+impl InterruptionLevel {
+    pub fn as_str(&self) -> &str {
+        match self {
+            InterruptionLevel::Active => "active",
+            InterruptionLevel::Critical => "critical",
+            InterruptionLevel::Passive => "passive",
+            InterruptionLevel::TimeSensitive => "time-sensitive",
+            InterruptionLevel::Unknown(s) => s,
+        }
+    }
+}
+
+impl From<String> for InterruptionLevel {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "active" => InterruptionLevel::Active,
+            "critical" => InterruptionLevel::Critical,
+            "passive" => InterruptionLevel::Passive,
+            "time-sensitive" => InterruptionLevel::TimeSensitive,
+            _ => InterruptionLevel::Unknown(s),
+        }
+    }
+}
+
+impl Serialize for InterruptionLevel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for InterruptionLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(InterruptionLevel::from)
+    }
 }
 
 #[cfg(test)]
@@ -31,6 +74,10 @@ mod tests {
             serde_json::to_string(&InterruptionLevel::TimeSensitive)?,
             "\"time-sensitive\""
         );
+        assert_eq!(
+            serde_json::to_string(&InterruptionLevel::Unknown("silent".to_string()))?,
+            "\"silent\""
+        );
         Ok(())
     }
 
@@ -52,6 +99,10 @@ mod tests {
             serde_json::from_str::<InterruptionLevel>("\"time-sensitive\"")?,
             InterruptionLevel::TimeSensitive
         );
+        assert_eq!(
+            serde_json::from_str::<InterruptionLevel>("\"silent\"")?,
+            InterruptionLevel::Unknown("silent".to_string())
+        );
         Ok(())
     }
 }
@@ -0,0 +1,14 @@
+use crate::{Details, ExpoPushReceiptId};
+
+/// A token reported as `DeviceNotRegistered` by a ticket or a receipt.
+///
+/// Sent to [`ExpoClientOptions::on_invalid_token`](crate::ExpoClientOptions::on_invalid_token).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidToken {
+    /// `None` when neither the request nor the error identifies the token.
+    pub token: Option<String>,
+    /// Set when the error comes from a receipt.
+    pub receipt_id: Option<ExpoPushReceiptId>,
+    pub message: String,
+    pub details: Details,
+}
@@ -0,0 +1,57 @@
+use crate::object::interruption_level::InterruptionLevel;
+use crate::object::sound::Sound;
+
+/// iOS-only fields of an [`ExpoPushMessage`](crate::ExpoPushMessage).
+///
+/// These are all the iOS fields of the Expo message format. It has no APNs `thread-id` or
+/// `apns-collapse-id`, so notifications cannot be grouped or collapsed through Expo.
+///
+/// <https://docs.expo.dev/push-notifications/sending-notifications/#message-request-format>
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct IosOptions {
+    pub subtitle: Option<String>,
+    pub sound: Option<Sound>,
+    pub badge: Option<u64>,
+    pub mutable_content: Option<bool>,
+    pub content_available: Option<bool>,
+    pub interruption_level: Option<InterruptionLevel>,
+}
+
+impl IosOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subtitle<S>(mut self, subtitle: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.subtitle = Some(subtitle.into());
+        self
+    }
+
+    pub fn sound(mut self, sound: Sound) -> Self {
+        self.sound = Some(sound);
+        self
+    }
+
+    pub fn badge(mut self, badge: u64) -> Self {
+        self.badge = Some(badge);
+        self
+    }
+
+    pub fn mutable_content(mut self, mutable_content: bool) -> Self {
+        self.mutable_content = Some(mutable_content);
+        self
+    }
+
+    pub fn content_available(mut self, content_available: bool) -> Self {
+        self.content_available = Some(content_available);
+        self
+    }
+
+    pub fn interruption_level(mut self, interruption_level: InterruptionLevel) -> Self {
+        self.interruption_level = Some(interruption_level);
+        self
+    }
+}
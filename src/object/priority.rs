@@ -1,9 +1,85 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Priority {
     Default,
     Normal,
     High,
+    /// A value not known to this crate, kept as is.
+    Unknown(String),
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Priority::Default => "default",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Unknown(s) => s,
+        }
+    }
+}
+
+impl From<String> for Priority {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "default" => Priority::Default,
+            "normal" => Priority::Normal,
+            "high" => Priority::High,
+            _ => Priority::Unknown(s),
+        }
+    }
+}
+
+impl Serialize for Priority {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Priority {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Priority::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialization() -> Result<(), serde_json::Error> {
+        assert_eq!(
+            serde_json::from_str::<Priority>("\"default\"")?,
+            Priority::Default
+        );
+        assert_eq!(
+            serde_json::from_str::<Priority>("\"normal\"")?,
+            Priority::Normal
+        );
+        assert_eq!(
+            serde_json::from_str::<Priority>("\"high\"")?,
+            Priority::High
+        );
+        assert_eq!(
+            serde_json::from_str::<Priority>("\"urgent\"")?,
+            Priority::Unknown("urgent".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialization_unknown() -> Result<(), serde_json::Error> {
+        assert_eq!(
+            serde_json::to_string(&Priority::Unknown("urgent".to_string()))?,
+            "\"urgent\""
+        );
+        Ok(())
+    }
 }
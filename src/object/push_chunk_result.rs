@@ -0,0 +1,13 @@
+use crate::{CustomError, ExpoPushMessage, ExpoPushTicket};
+
+/// The outcome of sending one chunk of messages, yielded by
+/// [`Expo::send_push_notifications_stream`](crate::Expo::send_push_notifications_stream).
+#[derive(Debug, PartialEq)]
+pub struct PushChunkResult {
+    /// Position of the chunk in the input, starting at 0.
+    pub index: usize,
+    pub messages: Vec<ExpoPushMessage>,
+    /// One ticket per recipient, not per message: a message sent to several tokens has as
+    /// many tickets, in the order of its `to`. Tickets follow the order of `messages`.
+    pub result: Result<Vec<ExpoPushTicket>, CustomError>,
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{CustomError, ExpoPushErrorReceipt, ExpoPushReceiptId};
+
+/// Timing of [`Expo::poll_receipts`](crate::Expo::poll_receipts).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceiptPollOptions {
+    /// Wait before the first query. Receipts are rarely available right after sending.
+    pub initial_delay: Duration,
+    /// Wait between two queries for the ids that are still missing.
+    pub interval: Duration,
+    /// Stop querying once this much time has passed since the call.
+    pub give_up_after: Duration,
+}
+
+impl Default for ReceiptPollOptions {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(15),
+            interval: Duration::from_secs(30),
+            give_up_after: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// The receipts collected by [`Expo::poll_receipts`](crate::Expo::poll_receipts).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReceiptPollReport {
+    pub ok: Vec<ExpoPushReceiptId>,
+    pub error: HashMap<ExpoPushReceiptId, ExpoPushErrorReceipt>,
+    /// Receipts with a `status` not known to this crate.
+    pub unknown: HashMap<ExpoPushReceiptId, String>,
+    /// Ids Expo had no receipt for when polling gave up.
+    pub missing: Vec<ExpoPushReceiptId>,
+    /// Queries that failed, e.g. on a transient server error. Their ids were queried again
+    /// at the next interval.
+    pub errors: Vec<CustomError>,
+}
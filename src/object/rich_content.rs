@@ -1,11 +1,25 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use serde_with::skip_serializing_none;
 
+use crate::error::ValidationError;
+
+/// Media attached to the notification and handled by the notification service extension.
+///
+/// Fields this crate does not model yet can be set with [`RichContent::extra`]; unknown
+/// fields are kept there when deserializing.
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct RichContent {
     pub image: Option<String>,
+    pub video: Option<String>,
+    pub audio: Option<String>,
+    /// Frame of a video attachment to use as thumbnail, in seconds.
+    pub thumbnail_time: Option<f64>,
+    pub thumbnail_hidden: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl RichContent {
@@ -20,12 +34,72 @@ impl RichContent {
         self.image = Some(image.into());
         self
     }
+
+    pub fn video<S>(mut self, video: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.video = Some(video.into());
+        self
+    }
+
+    pub fn audio<S>(mut self, audio: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.audio = Some(audio.into());
+        self
+    }
+
+    pub fn thumbnail_time(mut self, thumbnail_time: f64) -> Self {
+        self.thumbnail_time = Some(thumbnail_time);
+        self
+    }
+
+    pub fn thumbnail_hidden(mut self, thumbnail_hidden: bool) -> Self {
+        self.thumbnail_hidden = Some(thumbnail_hidden);
+        self
+    }
+
+    pub fn extra<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.extra.insert(key.into(), value.into());
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ValidationError> {
+        for url in [&self.image, &self.video, &self.audio]
+            .into_iter()
+            .flatten()
+        {
+            if !Self::is_valid_url(url) {
+                return Err(ValidationError::InvalidUrl);
+            }
+        }
+        if matches!(self.thumbnail_time, Some(t) if !t.is_finite() || t < 0.0) {
+            return Err(ValidationError::InvalidData);
+        }
+        Ok(())
+    }
+
+    fn is_valid_url(url: &str) -> bool {
+        let Some(rest) = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+        else {
+            return false;
+        };
+        let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        !host.is_empty() && !url.chars().any(char::is_whitespace)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::RichContent;
-    use serde_json;
+    use super::*;
 
     #[test]
     fn test_serialize() -> Result<(), serde_json::Error> {
@@ -42,4 +116,67 @@ mod tests {
         assert_eq!(serialized, r#"{}"#);
         Ok(())
     }
+
+    #[test]
+    fn test_serialize_media_and_extra() -> Result<(), serde_json::Error> {
+        let content = RichContent::new()
+            .video("https://example.com/video.mp4")
+            .audio("https://example.com/audio.m4a")
+            .thumbnail_time(1.5)
+            .thumbnail_hidden(false)
+            .extra("loop", true);
+        assert_eq!(
+            serde_json::to_value(&content)?,
+            serde_json::json!({
+                "video": "https://example.com/video.mp4",
+                "audio": "https://example.com/audio.m4a",
+                "thumbnailTime": 1.5,
+                "thumbnailHidden": false,
+                "loop": true
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_keeps_unknown_fields() -> Result<(), serde_json::Error> {
+        let content = serde_json::from_str::<RichContent>(
+            r#"{"image":"https://example.com/image.png","gif":"https://example.com/a.gif"}"#,
+        )?;
+        assert_eq!(
+            content,
+            RichContent::new()
+                .image("https://example.com/image.png")
+                .extra("gif", "https://example.com/a.gif")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            RichContent::new()
+                .image("https://example.com/image.png")
+                .video("http://example.com/video.mp4")
+                .validate(),
+            Ok(())
+        );
+        for url in [
+            "example.com/image.png",
+            "ftp://example.com/image.png",
+            "https://",
+            "https:///image.png",
+            "https://example.com/my image.png",
+        ] {
+            assert_eq!(
+                RichContent::new().audio(url).validate(),
+                Err(ValidationError::InvalidUrl),
+                "{url} should be rejected"
+            );
+        }
+        assert_eq!(
+            RichContent::new().thumbnail_time(-1.0).validate(),
+            Err(ValidationError::InvalidData)
+        );
+    }
 }
@@ -1,22 +1,36 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
 use crate::{CustomError, ExpoPushMessage};
 
+/// Messages to send, either owned or borrowed from the caller.
 #[derive(Debug, PartialEq, Clone)]
-pub struct SendPushNotificationsRequest(Vec<ExpoPushMessage>);
+pub struct SendPushNotificationsRequest<'a>(Cow<'a, [ExpoPushMessage]>);
 
-impl From<Vec<ExpoPushMessage>> for SendPushNotificationsRequest {
+impl From<Vec<ExpoPushMessage>> for SendPushNotificationsRequest<'_> {
     fn from(messages: Vec<ExpoPushMessage>) -> Self {
-        Self(messages)
+        Self(Cow::Owned(messages))
+    }
+}
+
+impl<'a> From<&'a [ExpoPushMessage]> for SendPushNotificationsRequest<'a> {
+    fn from(messages: &'a [ExpoPushMessage]) -> Self {
+        Self(Cow::Borrowed(messages))
     }
 }
 
-impl SendPushNotificationsRequest {
-    pub fn messages(&self) -> Vec<ExpoPushMessage> {
-        self.0.clone()
+impl SendPushNotificationsRequest<'_> {
+    pub fn messages(&self) -> &[ExpoPushMessage] {
+        &self.0
     }
 }
 
-impl SendPushNotificationsRequest {
-    fn new(messages: Vec<ExpoPushMessage>) -> Result<Self, CustomError> {
+impl<'a> SendPushNotificationsRequest<'a> {
+    fn new<M>(messages: M) -> Result<Self, CustomError>
+    where
+        M: Into<Cow<'a, [ExpoPushMessage]>>,
+    {
+        let messages = messages.into();
         if messages.is_empty() {
             return Err(CustomError::InvalidArgument(
                 "messages must not be empty".to_string(),
@@ -26,46 +40,387 @@ impl SendPushNotificationsRequest {
     }
 }
 
-impl serde::Serialize for SendPushNotificationsRequest {
+impl SendPushNotificationsRequest<'static> {
+    /// Collects a request from any iterator of messages or values convertible into them.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, CustomError>
+    where
+        I: IntoIterator,
+        I::Item: TryInto<ExpoPushMessage>,
+        <I::Item as TryInto<ExpoPushMessage>>::Error: Into<CustomError>,
+    {
+        Self::new(
+            iter.into_iter()
+                .map(|i| i.try_into().map_err(|e| e.into()))
+                .collect::<Result<Vec<ExpoPushMessage>, CustomError>>()?,
+        )
+    }
+}
+
+impl<'a> SendPushNotificationsRequest<'a> {
+    /// Removes tokens repeated within the `to` of a message, then recipients that an earlier
+    /// message already sends the same content to. Messages left without recipients are
+    /// removed.
+    ///
+    /// Messages with different idempotency keys are never merged, so that each key is still
+    /// recorded in the [`SentKeyStore`](crate::SentKeyStore). The messages are only copied
+    /// when something is removed.
+    pub fn deduplicate(self) -> (SendPushNotificationsRequest<'a>, DeduplicationReport) {
+        let mut report = DeduplicationReport::default();
+        // Each distinct content is kept once and recipients refer to it by id.
+        let mut contents = HashMap::new();
+        let mut sent = HashSet::new();
+        let mut deduplicated = vec![];
+        for (index, message) in self.0.iter().enumerate() {
+            // Every field but `to`, plus the idempotency key which is not serialized.
+            let content = serde_json::to_string(&message.with_recipients(vec![]))
+                .ok()
+                .map(|content| {
+                    let next = contents.len();
+                    let key = message.idempotency_key().map(str::to_string);
+                    *contents.entry((content, key)).or_insert(next)
+                });
+            let mut tokens = HashSet::new();
+            let mut to = vec![];
+            for token in message.to() {
+                let removed = RemovedRecipient {
+                    message_index: index,
+                    token: token.clone(),
+                };
+                if !tokens.insert(token) {
+                    report.duplicate_tokens.push(removed);
+                } else if content.is_some_and(|content| !sent.insert((token, content))) {
+                    report.duplicate_messages.push(removed);
+                } else {
+                    to.push(token.clone());
+                }
+            }
+            if to.is_empty() && !message.to().is_empty() {
+                report.removed_messages.push(index);
+            } else {
+                deduplicated.push((index, to));
+            }
+        }
+        if report.is_empty() {
+            return (self, report);
+        }
+        let messages = deduplicated
+            .into_iter()
+            .map(|(index, to)| self.0[index].with_recipients(to))
+            .collect::<Vec<_>>();
+        (Self(Cow::Owned(messages)), report)
+    }
+}
+
+/// What [`SendPushNotificationsRequest::deduplicate`] removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeduplicationReport {
+    /// Tokens repeated within the `to` of a message.
+    pub duplicate_tokens: Vec<RemovedRecipient>,
+    /// Recipients an earlier message already sends the same content to.
+    pub duplicate_messages: Vec<RemovedRecipient>,
+    /// Indexes of the messages left without recipients.
+    pub removed_messages: Vec<usize>,
+}
+
+impl DeduplicationReport {
+    pub fn is_empty(&self) -> bool {
+        self.duplicate_tokens.is_empty()
+            && self.duplicate_messages.is_empty()
+            && self.removed_messages.is_empty()
+    }
+}
+
+/// A recipient removed from the message at `message_index` in the original request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemovedRecipient {
+    pub message_index: usize,
+    pub token: String,
+}
+
+impl SendPushNotificationsRequest<'_> {
+    /// Returns a view of this request that writes `to` as a string for single-recipient
+    /// messages.
+    pub fn compact(&self) -> impl serde::Serialize + '_ {
+        SerializeMessages {
+            messages: &self.0,
+            compact: true,
+        }
+    }
+}
+
+impl serde::Serialize for SendPushNotificationsRequest<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        SerializeMessages {
+            messages: &self.0,
+            compact: false,
+        }
+        .serialize(serializer)
+    }
+}
+
+// A single message is sent as an object, several as an array.
+pub(crate) struct SerializeMessages<'a> {
+    pub(crate) messages: &'a [ExpoPushMessage],
+    pub(crate) compact: bool,
+}
+
+impl serde::Serialize for SerializeMessages<'_> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        if self.0.len() == 1 {
-            self.0[0].serialize(serializer)
-        } else {
-            self.0.serialize(serializer)
+        use serde::ser::SerializeSeq as _;
+
+        match (self.messages, self.compact) {
+            ([message], false) => message.serialize(serializer),
+            ([message], true) => message.compact().serialize(serializer),
+            (messages, false) => messages.serialize(serializer),
+            (messages, true) => {
+                let mut seq = serializer.serialize_seq(Some(messages.len()))?;
+                for message in messages {
+                    seq.serialize_element(&message.compact())?;
+                }
+                seq.end()
+            }
         }
     }
 }
 
-pub trait TryIntoSendPushNotificationsRequest {
+pub trait TryIntoSendPushNotificationsRequest<'a> {
     fn try_into_send_push_notifications_request(
         self,
-    ) -> Result<SendPushNotificationsRequest, CustomError>;
+    ) -> Result<SendPushNotificationsRequest<'a>, CustomError>;
 }
 
-impl TryIntoSendPushNotificationsRequest for ExpoPushMessage {
+impl TryIntoSendPushNotificationsRequest<'_> for ExpoPushMessage {
     fn try_into_send_push_notifications_request(
         self,
-    ) -> Result<SendPushNotificationsRequest, CustomError> {
+    ) -> Result<SendPushNotificationsRequest<'static>, CustomError> {
         SendPushNotificationsRequest::new(vec![self])
     }
 }
 
-impl<I> TryIntoSendPushNotificationsRequest for I
+impl<'a> TryIntoSendPushNotificationsRequest<'a> for &'a ExpoPushMessage {
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'a>, CustomError> {
+        SendPushNotificationsRequest::new(std::slice::from_ref(self))
+    }
+}
+
+impl<'a> TryIntoSendPushNotificationsRequest<'a> for &'a [ExpoPushMessage] {
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'a>, CustomError> {
+        SendPushNotificationsRequest::new(self)
+    }
+}
+
+impl<'a> TryIntoSendPushNotificationsRequest<'a> for &'a Vec<ExpoPushMessage> {
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'a>, CustomError> {
+        SendPushNotificationsRequest::new(self.as_slice())
+    }
+}
+
+impl TryIntoSendPushNotificationsRequest<'_> for Vec<ExpoPushMessage> {
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'static>, CustomError> {
+        SendPushNotificationsRequest::new(self)
+    }
+}
+
+impl<const N: usize> TryIntoSendPushNotificationsRequest<'_> for [ExpoPushMessage; N] {
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'static>, CustomError> {
+        SendPushNotificationsRequest::new(Vec::from(self))
+    }
+}
+
+impl<T> TryIntoSendPushNotificationsRequest<'_> for std::vec::IntoIter<T>
 where
-    I: IntoIterator,
-    <I as IntoIterator>::Item: TryInto<ExpoPushMessage>,
-    <<I as IntoIterator>::Item as TryInto<ExpoPushMessage>>::Error: Into<CustomError>,
+    T: TryInto<ExpoPushMessage>,
+    T::Error: Into<CustomError>,
 {
     fn try_into_send_push_notifications_request(
         self,
-    ) -> Result<SendPushNotificationsRequest, CustomError> {
-        SendPushNotificationsRequest::new(
-            self.into_iter()
-                .map(|i| i.try_into().map_err(|e| e.into()))
-                .collect::<Result<Vec<ExpoPushMessage>, CustomError>>()?,
-        )
+    ) -> Result<SendPushNotificationsRequest<'static>, CustomError> {
+        SendPushNotificationsRequest::try_from_iter(self)
+    }
+}
+
+impl<T> TryIntoSendPushNotificationsRequest<'_> for std::iter::Once<T>
+where
+    T: TryInto<ExpoPushMessage>,
+    T::Error: Into<CustomError>,
+{
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'static>, CustomError> {
+        SendPushNotificationsRequest::try_from_iter(self)
+    }
+}
+
+impl<I, F, T> TryIntoSendPushNotificationsRequest<'_> for std::iter::Map<I, F>
+where
+    I: Iterator,
+    F: FnMut(I::Item) -> T,
+    T: TryInto<ExpoPushMessage>,
+    T::Error: Into<CustomError>,
+{
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'static>, CustomError> {
+        SendPushNotificationsRequest::try_from_iter(self)
+    }
+}
+
+impl<'a> TryIntoSendPushNotificationsRequest<'a> for SendPushNotificationsRequest<'a> {
+    fn try_into_send_push_notifications_request(
+        self,
+    ) -> Result<SendPushNotificationsRequest<'a>, CustomError> {
+        SendPushNotificationsRequest::new(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrowed_request_does_not_copy() -> anyhow::Result<()> {
+        let messages = vec![
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+            ExpoPushMessage::builder(["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"]).build()?,
+        ];
+
+        let request = messages
+            .as_slice()
+            .try_into_send_push_notifications_request()?;
+        assert!(std::ptr::eq(request.messages(), messages.as_slice()));
+
+        let request = (&messages).try_into_send_push_notifications_request()?;
+        assert!(std::ptr::eq(request.messages(), messages.as_slice()));
+
+        let request = (&messages[0]).try_into_send_push_notifications_request()?;
+        assert!(std::ptr::eq(&request.messages()[0], &messages[0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deduplicate() -> anyhow::Result<()> {
+        let x = "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]";
+        let y = "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]";
+        let messages = vec![
+            ExpoPushMessage::builder([x, y, x]).title("a").build()?,
+            ExpoPushMessage::builder([y]).title("a").build()?,
+            ExpoPushMessage::builder([y]).title("b").build()?,
+        ];
+
+        let (request, report) = messages
+            .as_slice()
+            .try_into_send_push_notifications_request()?
+            .deduplicate();
+        assert_eq!(
+            request.messages(),
+            [
+                ExpoPushMessage::builder([x, y]).title("a").build()?,
+                ExpoPushMessage::builder([y]).title("b").build()?,
+            ]
+        );
+        assert_eq!(
+            report,
+            DeduplicationReport {
+                duplicate_tokens: vec![RemovedRecipient {
+                    message_index: 0,
+                    token: x.to_string()
+                }],
+                duplicate_messages: vec![RemovedRecipient {
+                    message_index: 1,
+                    token: y.to_string()
+                }],
+                removed_messages: vec![1],
+            }
+        );
+
+        let (request, report) = messages[2..]
+            .try_into_send_push_notifications_request()?
+            .deduplicate();
+        assert!(report.is_empty());
+        assert!(std::ptr::eq(request.messages(), &messages[2..]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deduplicate_keeps_idempotency_keys() -> anyhow::Result<()> {
+        let x = "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]";
+        let messages = vec![
+            ExpoPushMessage::builder([x])
+                .title("a")
+                .idempotency_key("1")
+                .build()?,
+            ExpoPushMessage::builder([x])
+                .title("a")
+                .idempotency_key("2")
+                .build()?,
+            ExpoPushMessage::builder([x])
+                .title("a")
+                .idempotency_key("2")
+                .build()?,
+        ];
+
+        let (request, report) = messages
+            .as_slice()
+            .try_into_send_push_notifications_request()?
+            .deduplicate();
+        assert_eq!(request.messages(), &messages[..2]);
+        assert_eq!(report.removed_messages, [2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_from_iterators() -> anyhow::Result<()> {
+        let message =
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?;
+
+        let request =
+            std::iter::once(message.clone()).try_into_send_push_notifications_request()?;
+        assert_eq!(request.messages(), std::slice::from_ref(&message));
+
+        let request = vec![message.clone(), message.clone()]
+            .into_iter()
+            .try_into_send_push_notifications_request()?;
+        assert_eq!(request.messages().len(), 2);
+
+        let request = ["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]
+            .into_iter()
+            .map(|to| ExpoPushMessage::builder([to]).build().expect("valid token"))
+            .try_into_send_push_notifications_request()?;
+        assert_eq!(request.messages(), std::slice::from_ref(&message));
+
+        let request = SendPushNotificationsRequest::try_from_iter(
+            [message.clone()].into_iter().filter(|_| true),
+        )?;
+        assert_eq!(request.messages(), [message]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_request() {
+        let messages: Vec<ExpoPushMessage> = vec![];
+        assert_eq!(
+            messages
+                .as_slice()
+                .try_into_send_push_notifications_request(),
+            Err(CustomError::InvalidArgument(
+                "messages must not be empty".to_string()
+            ))
+        );
     }
 }
@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+/// Resending of the notifications whose ticket failed with a retryable error, see
+/// [`DetailsErrorType::is_retryable`](crate::DetailsErrorType::is_retryable).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TicketRetryOptions {
+    /// Number of sends including the first one.
+    pub max_attempts: u32,
+    /// Wait before the first resend, doubled after each resend.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for TicketRetryOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use futures::StreamExt as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    jsonl_store::JsonlFile, Clock, CustomError, Expo, ExpoPushMessage, SendResult, SystemClock,
+};
+
+/// A message waiting in an [`Outbox`], or sent with its results.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "OutboxRecord", into = "OutboxRecord")]
+pub struct OutboxEntry {
+    pub id: u64,
+    pub message: ExpoPushMessage,
+    pub send_at: SystemTime,
+    /// `None` until the message is sent.
+    pub sent: Option<OutboxSent>,
+}
+
+// Stored shape of an `OutboxEntry`. The idempotency key is kept next to the message, which
+// leaves it out of its serialized form since it is never sent to Expo.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OutboxRecord {
+    id: u64,
+    message: ExpoPushMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    idempotency_key: Option<String>,
+    send_at: SystemTime,
+    sent: Option<OutboxSent>,
+}
+
+impl From<OutboxEntry> for OutboxRecord {
+    fn from(entry: OutboxEntry) -> Self {
+        Self {
+            id: entry.id,
+            idempotency_key: entry.message.idempotency_key().map(str::to_string),
+            message: entry.message,
+            send_at: entry.send_at,
+            sent: entry.sent,
+        }
+    }
+}
+
+impl From<OutboxRecord> for OutboxEntry {
+    fn from(record: OutboxRecord) -> Self {
+        Self {
+            id: record.id,
+            message: record.message.with_idempotency_key(record.idempotency_key),
+            send_at: record.send_at,
+            sent: record.sent,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxSent {
+    pub sent_at: SystemTime,
+    /// One per recipient, in the order of `message.to()`.
+    pub results: Vec<SendResult>,
+}
+
+/// Messages to send at a later time, kept until sent by an [`OutboxWorker`].
+pub trait Outbox: Send + Sync {
+    /// Returns the id of the new entry.
+    fn enqueue(&self, message: ExpoPushMessage, send_at: SystemTime) -> Result<u64, CustomError>;
+
+    fn get(&self, id: u64) -> Result<Option<OutboxEntry>, CustomError>;
+
+    /// Returns at most `limit` unsent entries whose `send_at` is not after `now`, earliest
+    /// first.
+    fn list_due(&self, now: SystemTime, limit: usize) -> Result<Vec<OutboxEntry>, CustomError>;
+
+    fn mark_sent(&self, id: u64, sent: OutboxSent) -> Result<(), CustomError>;
+
+    /// Removes and returns the entries sent before `before`.
+    fn remove_sent(&self, before: SystemTime) -> Result<Vec<OutboxEntry>, CustomError>;
+}
+
+/// An [`Outbox`] kept in memory, lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryOutbox {
+    entries: Mutex<HashMap<u64, OutboxEntry>>,
+    // Highest id handed out, so that ids are not reused once their entry is removed.
+    last_id: AtomicU64,
+}
+
+impl MemoryOutbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<u64, OutboxEntry>>, CustomError> {
+        self.entries
+            .lock()
+            .map_err(|e| CustomError::StoreErr(e.to_string()))
+    }
+
+    fn put(&self, entry: OutboxEntry) -> Result<(), CustomError> {
+        self.reserve(entry.id);
+        self.lock()?.insert(entry.id, entry);
+        Ok(())
+    }
+
+    fn next_id(&self) -> u64 {
+        self.last_id.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn last_id(&self) -> u64 {
+        self.last_id.load(Ordering::SeqCst)
+    }
+
+    /// Makes sure ids up to `id` are never handed out again.
+    fn reserve(&self, id: u64) {
+        self.last_id.fetch_max(id, Ordering::SeqCst);
+    }
+
+    fn sent(&self, id: u64, sent: OutboxSent) -> Result<OutboxEntry, CustomError> {
+        let mut entries = self.lock()?;
+        let entry = entries
+            .get_mut(&id)
+            .ok_or_else(|| CustomError::StoreErr(format!("No outbox entry {id}")))?;
+        entry.sent = Some(sent);
+        Ok(entry.clone())
+    }
+}
+
+impl Outbox for MemoryOutbox {
+    fn enqueue(&self, message: ExpoPushMessage, send_at: SystemTime) -> Result<u64, CustomError> {
+        let mut entries = self.lock()?;
+        let id = self.next_id();
+        entries.insert(
+            id,
+            OutboxEntry {
+                id,
+                message,
+                send_at,
+                sent: None,
+            },
+        );
+        Ok(id)
+    }
+
+    fn get(&self, id: u64) -> Result<Option<OutboxEntry>, CustomError> {
+        Ok(self.lock()?.get(&id).cloned())
+    }
+
+    fn list_due(&self, now: SystemTime, limit: usize) -> Result<Vec<OutboxEntry>, CustomError> {
+        let mut due = self
+            .lock()?
+            .values()
+            .filter(|entry| entry.sent.is_none() && entry.send_at <= now)
+            .cloned()
+            .collect::<Vec<_>>();
+        due.sort_by(|a, b| a.send_at.cmp(&b.send_at).then_with(|| a.id.cmp(&b.id)));
+        due.truncate(limit);
+        Ok(due)
+    }
+
+    fn mark_sent(&self, id: u64, sent: OutboxSent) -> Result<(), CustomError> {
+        self.sent(id, sent).map(|_| ())
+    }
+
+    fn remove_sent(&self, before: SystemTime) -> Result<Vec<OutboxEntry>, CustomError> {
+        let mut entries = self.lock()?;
+        let removed = entries
+            .values()
+            .filter(|entry| matches!(&entry.sent, Some(sent) if sent.sent_at < before))
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        Ok(removed.iter().filter_map(|id| entries.remove(id)).collect())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum FileEntry {
+    Put(Box<OutboxEntry>),
+    /// Highest id handed out, written when compaction may drop the entry that had it.
+    LastId(u64),
+}
+
+/// An [`Outbox`] backed by an append-only JSON Lines file, so that messages survive a
+/// restart.
+///
+/// Every change is appended to the file and replayed on [`FileOutbox::open`].
+/// [`Outbox::remove_sent`] compacts the file.
+#[derive(Debug)]
+pub struct FileOutbox {
+    file: JsonlFile,
+    entries: MemoryOutbox,
+}
+
+impl FileOutbox {
+    pub fn open<P>(path: P) -> Result<Self, CustomError>
+    where
+        P: AsRef<Path>,
+    {
+        let entries = MemoryOutbox::new();
+        let file = JsonlFile::open(path.as_ref(), |entry| match entry {
+            FileEntry::Put(entry) => entries.put(*entry),
+            FileEntry::LastId(id) => {
+                entries.reserve(id);
+                Ok(())
+            }
+        })?;
+        Ok(Self { file, entries })
+    }
+}
+
+impl Outbox for FileOutbox {
+    fn enqueue(&self, message: ExpoPushMessage, send_at: SystemTime) -> Result<u64, CustomError> {
+        let mut file = self.file.lock()?;
+        let entry = OutboxEntry {
+            id: self.entries.next_id(),
+            message,
+            send_at,
+            sent: None,
+        };
+        let id = entry.id;
+        file.append(&[FileEntry::Put(Box::new(entry.clone()))])?;
+        self.entries.put(entry)?;
+        Ok(id)
+    }
+
+    fn get(&self, id: u64) -> Result<Option<OutboxEntry>, CustomError> {
+        self.entries.get(id)
+    }
+
+    fn list_due(&self, now: SystemTime, limit: usize) -> Result<Vec<OutboxEntry>, CustomError> {
+        self.entries.list_due(now, limit)
+    }
+
+    fn mark_sent(&self, id: u64, sent: OutboxSent) -> Result<(), CustomError> {
+        let mut file = self.file.lock()?;
+        let entry = self.entries.sent(id, sent)?;
+        file.append(&[FileEntry::Put(Box::new(entry))])
+    }
+
+    fn remove_sent(&self, before: SystemTime) -> Result<Vec<OutboxEntry>, CustomError> {
+        let mut file = self.file.lock()?;
+        let removed = self.entries.remove_sent(before)?;
+        let remaining = std::iter::once(FileEntry::LastId(self.entries.last_id()))
+            .chain(
+                self.entries
+                    .lock()?
+                    .values()
+                    .map(|entry| FileEntry::Put(Box::new(entry.clone()))),
+            )
+            .collect::<Vec<_>>();
+        self.file.compact(&mut file, &remaining)?;
+        Ok(removed)
+    }
+}
+
+/// Sends the due messages of an [`Outbox`] with [`Expo::send_push_notifications_stream`] and
+/// records their results in it.
+///
+/// A message is marked sent only after Expo returned its tickets, so a crash in between may
+/// send it again after a restart.
+pub struct OutboxWorker {
+    expo: Expo,
+    outbox: Arc<dyn Outbox>,
+    clock: Arc<dyn Clock>,
+    poll_interval: Duration,
+    batch_size: usize,
+}
+
+impl OutboxWorker {
+    pub fn new(expo: Expo, outbox: Arc<dyn Outbox>) -> Self {
+        Self {
+            expo,
+            outbox,
+            clock: Arc::new(SystemClock),
+            poll_interval: Duration::from_secs(5),
+            batch_size: 1000,
+        }
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Wait between two checks of the outbox. Defaults to 5 seconds.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Messages sent per check at most. Defaults to 1000.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sends the messages due now chunk by chunk and returns the ids of those sent.
+    ///
+    /// Each chunk is marked sent as soon as Expo accepts it. The messages of a chunk that
+    /// fails stay due and the first such error is returned once the others are sent.
+    pub async fn run_once(&self) -> Result<Vec<u64>, CustomError> {
+        let due = self.outbox.list_due(self.clock.now(), self.batch_size)?;
+        let mut ids = due
+            .iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>()
+            .into_iter();
+        let mut chunks =
+            std::pin::pin!(self
+                .expo
+                .send_push_notifications_stream(futures::stream::iter(
+                    due.into_iter().map(|entry| entry.message)
+                )));
+        let mut sent = vec![];
+        let mut error = None;
+        while let Some(chunk) = chunks.next().await {
+            let ids = ids.by_ref().take(chunk.messages.len()).collect::<Vec<_>>();
+            let tickets = match chunk.result {
+                Ok(tickets) => tickets,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    continue;
+                }
+            };
+            let sent_at = self.clock.now();
+            let mut tickets = tickets.as_slice();
+            for (id, message) in ids.into_iter().zip(&chunk.messages) {
+                let (own, rest) = tickets.split_at(message.to().len().min(tickets.len()));
+                tickets = rest;
+                self.outbox.mark_sent(
+                    id,
+                    OutboxSent {
+                        sent_at,
+                        results: SendResult::from_tickets(std::slice::from_ref(message), own),
+                    },
+                )?;
+                sent.push(id);
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(sent),
+        }
+    }
+
+    /// Checks the outbox forever. Failed sends are retried at the next check; only an
+    /// outbox error stops the loop.
+    pub async fn run(&self) -> Result<(), CustomError> {
+        loop {
+            if let Err(e @ CustomError::StoreErr(_)) = self.run_once().await {
+                return Err(e);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{expo_client::SplitChunkServer, jsonl_store::TempPath, ManualClock};
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+    }
+
+    fn message(token: &str) -> anyhow::Result<ExpoPushMessage> {
+        Ok(ExpoPushMessage::builder([token]).build()?)
+    }
+
+    fn assert_outbox(outbox: &dyn Outbox) -> anyhow::Result<()> {
+        let later = outbox.enqueue(
+            message("ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]")?,
+            at(60),
+        )?;
+        let sooner =
+            outbox.enqueue(message("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]")?, at(0))?;
+        assert_ne!(later, sooner);
+
+        let ids = |entries: Vec<OutboxEntry>| entries.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(outbox.list_due(at(30), 10)?), [sooner]);
+        assert_eq!(ids(outbox.list_due(at(60), 10)?), [sooner, later]);
+        assert_eq!(ids(outbox.list_due(at(60), 1)?), [sooner]);
+
+        let sent = OutboxSent {
+            sent_at: at(61),
+            results: vec![],
+        };
+        outbox.mark_sent(sooner, sent.clone())?;
+        assert_eq!(ids(outbox.list_due(at(60), 10)?), [later]);
+        assert_eq!(outbox.get(sooner)?.and_then(|e| e.sent), Some(sent));
+
+        assert_eq!(ids(outbox.remove_sent(at(61))?), Vec::<u64>::new());
+        assert_eq!(ids(outbox.remove_sent(at(62))?), [sooner]);
+        assert_eq!(outbox.get(sooner)?, None);
+
+        // The id of a removed entry is not handed out again.
+        let fresh = outbox.enqueue(message("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]")?, at(0))?;
+        assert!(fresh > sooner && fresh > later);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_outbox() -> anyhow::Result<()> {
+        assert_outbox(&MemoryOutbox::new())
+    }
+
+    #[test]
+    fn test_file_outbox() -> anyhow::Result<()> {
+        let path = TempPath::new("outbox.jsonl");
+
+        assert_outbox(&FileOutbox::open(&path)?)?;
+
+        // The unsent messages survive a reopen and ids keep increasing.
+        let outbox = FileOutbox::open(&path)?;
+        let pending = outbox.list_due(at(60), 10)?;
+        assert_eq!(pending.len(), 2);
+        let last = pending.iter().map(|e| e.id).max().unwrap_or_default();
+        outbox.mark_sent(
+            last,
+            OutboxSent {
+                sent_at: at(0),
+                results: vec![],
+            },
+        )?;
+        outbox.remove_sent(at(1))?;
+
+        // Even once the entry with the highest id was compacted away.
+        let outbox = FileOutbox::open(&path)?;
+        let keyed = ExpoPushMessage::builder(["ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]"])
+            .idempotency_key("k1")
+            .build()?;
+        let id = outbox.enqueue(keyed, at(0))?;
+        assert!(id > last);
+
+        // So does the idempotency key, which the message alone does not serialize.
+        let entry = FileOutbox::open(&path)?.get(id)?;
+        assert_eq!(
+            entry
+                .as_ref()
+                .and_then(|entry| entry.message.idempotency_key()),
+            Some("k1")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_worker() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .expect(1)
+            .create();
+        let outbox = Arc::new(MemoryOutbox::new());
+        let clock = Arc::new(ManualClock::new(at(0)));
+        let worker =
+            OutboxWorker::new(Expo::new_with_base_url(None, &server.url()), outbox.clone())
+                .clock(clock.clone());
+
+        let id = outbox.enqueue(
+            message("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]")?,
+            at(60),
+        )?;
+        assert_eq!(worker.run_once().await?, Vec::<u64>::new());
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(worker.run_once().await?, [id]);
+        let sent = outbox.get(id)?.and_then(|entry| entry.sent);
+        assert_eq!(
+            sent.map(|sent| sent.results[0].receipt_id.clone()),
+            Some(Some("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX".to_string()))
+        );
+        assert_eq!(worker.run_once().await?, Vec::<u64>::new());
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_worker_marks_accepted_chunks() -> anyhow::Result<()> {
+        let server = SplitChunkServer::new().await;
+        let outbox = Arc::new(MemoryOutbox::new());
+        let expo = server.expo();
+        let worker =
+            OutboxWorker::new(expo, outbox.clone()).clock(Arc::new(ManualClock::new(at(0))));
+        for _ in 0..SplitChunkServer::CHUNK_SIZE {
+            outbox.enqueue(message("ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]")?, at(0))?;
+        }
+        let last = outbox.enqueue(message("ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]")?, at(0))?;
+
+        assert!(worker.run_once().await.is_err());
+        let due = outbox.list_due(at(0), 1000)?;
+        assert_eq!(due.iter().map(|entry| entry.id).collect::<Vec<_>>(), [last]);
+        server.assert();
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{jsonl_store::JsonlFile, CustomError, ExpoPushReceiptId};
+
+/// Expo keeps receipts for 24 hours after sending.
+pub const RECEIPT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A receipt id waiting to be checked, with the token it was issued for.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingReceipt {
+    pub id: ExpoPushReceiptId,
+    pub token: String,
+    pub sent_at: SystemTime,
+}
+
+/// Persists receipt ids between sending and checking receipts.
+///
+/// Set [`ExpoClientOptions::receipt_store`](crate::ExpoClientOptions::receipt_store) to
+/// record every `ExpoPushTicket::Ok` automatically.
+pub trait ReceiptStore: Send + Sync {
+    fn put(&self, receipts: Vec<PendingReceipt>) -> Result<(), CustomError>;
+
+    /// Returns the receipts sent at least `delay` before `now` that have not expired,
+    /// oldest first.
+    fn list_due(
+        &self,
+        now: SystemTime,
+        delay: Duration,
+    ) -> Result<Vec<PendingReceipt>, CustomError>;
+
+    fn mark_resolved(&self, ids: &[ExpoPushReceiptId]) -> Result<(), CustomError>;
+
+    /// Removes and returns the receipts older than [`RECEIPT_RETENTION`], which Expo no
+    /// longer has.
+    fn expire(&self, now: SystemTime) -> Result<Vec<PendingReceipt>, CustomError>;
+}
+
+fn is_expired(receipt: &PendingReceipt, now: SystemTime) -> bool {
+    receipt.sent_at + RECEIPT_RETENTION <= now
+}
+
+/// A [`ReceiptStore`] kept in memory, lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryReceiptStore {
+    receipts: Mutex<HashMap<ExpoPushReceiptId, PendingReceipt>>,
+}
+
+impl MemoryReceiptStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<ExpoPushReceiptId, PendingReceipt>>, CustomError>
+    {
+        self.receipts
+            .lock()
+            .map_err(|e| CustomError::StoreErr(e.to_string()))
+    }
+}
+
+impl ReceiptStore for MemoryReceiptStore {
+    fn put(&self, receipts: Vec<PendingReceipt>) -> Result<(), CustomError> {
+        let mut map = self.lock()?;
+        for receipt in receipts {
+            map.insert(receipt.id.clone(), receipt);
+        }
+        Ok(())
+    }
+
+    fn list_due(
+        &self,
+        now: SystemTime,
+        delay: Duration,
+    ) -> Result<Vec<PendingReceipt>, CustomError> {
+        let mut due = self
+            .lock()?
+            .values()
+            .filter(|receipt| receipt.sent_at + delay <= now && !is_expired(receipt, now))
+            .cloned()
+            .collect::<Vec<_>>();
+        due.sort_by(|a, b| a.sent_at.cmp(&b.sent_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(due)
+    }
+
+    fn mark_resolved(&self, ids: &[ExpoPushReceiptId]) -> Result<(), CustomError> {
+        let mut map = self.lock()?;
+        for id in ids {
+            map.remove(id);
+        }
+        Ok(())
+    }
+
+    fn expire(&self, now: SystemTime) -> Result<Vec<PendingReceipt>, CustomError> {
+        let mut map = self.lock()?;
+        let expired = map
+            .values()
+            .filter(|receipt| is_expired(receipt, now))
+            .map(|receipt| receipt.id.clone())
+            .collect::<Vec<_>>();
+        Ok(expired.iter().filter_map(|id| map.remove(id)).collect())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum FileEntry {
+    Put(PendingReceipt),
+    Resolved(ExpoPushReceiptId),
+}
+
+/// A [`ReceiptStore`] backed by an append-only JSON Lines file.
+///
+/// Every change is appended to the file and replayed on [`FileReceiptStore::open`].
+/// [`ReceiptStore::expire`] compacts the file down to the receipts still pending.
+#[derive(Debug)]
+pub struct FileReceiptStore {
+    file: JsonlFile,
+    receipts: MemoryReceiptStore,
+}
+
+impl FileReceiptStore {
+    pub fn open<P>(path: P) -> Result<Self, CustomError>
+    where
+        P: AsRef<Path>,
+    {
+        let receipts = MemoryReceiptStore::new();
+        let file = JsonlFile::open(path.as_ref(), |entry| match entry {
+            FileEntry::Put(receipt) => receipts.put(vec![receipt]),
+            FileEntry::Resolved(id) => receipts.mark_resolved(&[id]),
+        })?;
+        Ok(Self { file, receipts })
+    }
+}
+
+impl ReceiptStore for FileReceiptStore {
+    fn put(&self, receipts: Vec<PendingReceipt>) -> Result<(), CustomError> {
+        let mut file = self.file.lock()?;
+        let entries = receipts.into_iter().map(FileEntry::Put).collect::<Vec<_>>();
+        file.append(&entries)?;
+        self.receipts.put(
+            entries
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    FileEntry::Put(receipt) => Some(receipt),
+                    FileEntry::Resolved(_) => None,
+                })
+                .collect(),
+        )
+    }
+
+    fn list_due(
+        &self,
+        now: SystemTime,
+        delay: Duration,
+    ) -> Result<Vec<PendingReceipt>, CustomError> {
+        self.receipts.list_due(now, delay)
+    }
+
+    fn mark_resolved(&self, ids: &[ExpoPushReceiptId]) -> Result<(), CustomError> {
+        let mut file = self.file.lock()?;
+        let entries = ids
+            .iter()
+            .cloned()
+            .map(FileEntry::Resolved)
+            .collect::<Vec<_>>();
+        file.append(&entries)?;
+        self.receipts.mark_resolved(ids)
+    }
+
+    fn expire(&self, now: SystemTime) -> Result<Vec<PendingReceipt>, CustomError> {
+        let mut file = self.file.lock()?;
+        let expired = self.receipts.expire(now)?;
+        let pending = self
+            .receipts
+            .lock()?
+            .values()
+            .cloned()
+            .map(FileEntry::Put)
+            .collect::<Vec<_>>();
+        self.file.compact(&mut file, &pending)?;
+        Ok(expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use super::*;
+    use crate::jsonl_store::TempPath;
+
+    fn receipt(id: &str, sent_at: SystemTime) -> anyhow::Result<PendingReceipt> {
+        Ok(PendingReceipt {
+            id: ExpoPushReceiptId::from_str(id)?,
+            token: "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]".to_string(),
+            sent_at,
+        })
+    }
+
+    fn assert_store(store: &dyn ReceiptStore) -> anyhow::Result<()> {
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let min = Duration::from_secs(60);
+        store.put(vec![
+            receipt("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX", t0)?,
+            receipt("YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY", t0 + min)?,
+            receipt("ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ", t0 + 2 * min)?,
+        ])?;
+
+        let due = store.list_due(t0 + 16 * min, 15 * min)?;
+        assert_eq!(
+            due.iter().map(|r| r.id.to_string()).collect::<Vec<_>>(),
+            [
+                "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX",
+                "YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY"
+            ]
+        );
+
+        store.mark_resolved(&[ExpoPushReceiptId::from_str(
+            "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX",
+        )?])?;
+        assert_eq!(
+            store.list_due(t0 + 17 * min, 15 * min)?,
+            vec![
+                receipt("YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY", t0 + min)?,
+                receipt("ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ", t0 + 2 * min)?,
+            ]
+        );
+
+        let later = t0 + RECEIPT_RETENTION + min;
+        assert_eq!(
+            store.list_due(later, 15 * min)?,
+            vec![receipt(
+                "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ",
+                t0 + 2 * min
+            )?]
+        );
+        assert_eq!(
+            store.expire(later)?,
+            vec![receipt("YYYYYYYY-YYYY-YYYY-YYYY-YYYYYYYYYYYY", t0 + min)?]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_receipt_store() -> anyhow::Result<()> {
+        assert_store(&MemoryReceiptStore::new())
+    }
+
+    #[test]
+    fn test_file_receipt_store() -> anyhow::Result<()> {
+        let path = TempPath::new("receipts.jsonl");
+
+        assert_store(&FileReceiptStore::open(&path)?)?;
+
+        // Only the receipt still pending survives a reopen.
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let later = t0 + RECEIPT_RETENTION + Duration::from_secs(60);
+        assert_eq!(
+            FileReceiptStore::open(&path)?.list_due(later, Duration::ZERO)?,
+            vec![receipt(
+                "ZZZZZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZZZZZZZZZ",
+                t0 + Duration::from_secs(120)
+            )?]
+        );
+        Ok(())
+    }
+}
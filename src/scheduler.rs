@@ -0,0 +1,279 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use futures::StreamExt as _;
+use jiff::{civil::Time, tz::TimeZone, Timestamp};
+
+use crate::{
+    Clock, CustomError, Expo, ExpoPushMessage, ExpoPushTicket, PushChunkResult, SystemClock,
+};
+
+/// A daily window of local time during which notifications are held back.
+///
+/// The window may wrap around midnight, e.g. from 22:00 to 08:00.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuietHours {
+    start: Time,
+    end: Time,
+}
+
+impl QuietHours {
+    /// `start` and `end` are `(hour, minute)` in local time; `start` is inclusive and `end`
+    /// exclusive.
+    pub fn new(start: (i8, i8), end: (i8, i8)) -> Result<Self, CustomError> {
+        let time = |(hour, minute): (i8, i8)| {
+            Time::new(hour, minute, 0, 0)
+                .map_err(|e| CustomError::InvalidArgument(format!("Invalid time: {e}")))
+        };
+        Ok(Self {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+
+    fn contains(&self, time: Time) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+
+    /// The first instant at or after `now` outside the quiet hours in `time_zone`.
+    pub fn next_allowed(
+        &self,
+        now: SystemTime,
+        time_zone: &str,
+    ) -> Result<SystemTime, CustomError> {
+        let tz = TimeZone::get(time_zone)
+            .map_err(|e| CustomError::InvalidArgument(format!("Invalid time zone: {e}")))?;
+        let now = Timestamp::try_from(now)
+            .map_err(|e| CustomError::InvalidArgument(e.to_string()))?
+            .to_zoned(tz.clone());
+        if !self.contains(now.time()) {
+            return Ok(now.timestamp().into());
+        }
+        let date = if self.start > self.end && now.time() >= self.start {
+            now.date()
+                .tomorrow()
+                .map_err(|e| CustomError::InvalidArgument(e.to_string()))?
+        } else {
+            now.date()
+        };
+        date.to_datetime(self.end)
+            .to_zoned(tz)
+            .map(|end| end.timestamp().into())
+            .map_err(|e| CustomError::InvalidArgument(e.to_string()))
+    }
+}
+
+/// Holds messages until the quiet hours of their recipient's time zone are over, then sends
+/// them with [`Expo::send_push_notifications`].
+pub struct Scheduler {
+    expo: Expo,
+    clock: Arc<dyn Clock>,
+    pending: Mutex<Vec<(SystemTime, ExpoPushMessage)>>,
+}
+
+impl Scheduler {
+    pub fn new(expo: Expo) -> Self {
+        Self::with_clock(expo, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(expo: Expo, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            expo,
+            clock,
+            pending: Mutex::new(vec![]),
+        }
+    }
+
+    /// Holds `message` until `quiet_hours` are over in `time_zone`, an IANA name such as
+    /// `Europe/Paris`. Returns when it becomes due.
+    pub fn schedule(
+        &self,
+        message: ExpoPushMessage,
+        time_zone: &str,
+        quiet_hours: &QuietHours,
+    ) -> Result<SystemTime, CustomError> {
+        let due = quiet_hours.next_allowed(self.clock.now(), time_zone)?;
+        self.lock().push((due, message));
+        Ok(due)
+    }
+
+    /// When the earliest message held becomes due.
+    pub fn next_due(&self) -> Option<SystemTime> {
+        self.lock().iter().map(|(due, _)| *due).min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Sends the messages that are due, chunk by chunk. The messages of a chunk that fails
+    /// are held again; the chunks Expo accepted are not.
+    pub async fn dispatch_due(&self) -> Vec<PushChunkResult> {
+        let now = self.clock.now();
+        let due = {
+            let mut pending = self.lock();
+            let (due, held) = pending
+                .drain(..)
+                .partition::<Vec<_>, _>(|(at, _)| *at <= now);
+            *pending = held;
+            due
+        };
+        let mut due_at = due
+            .iter()
+            .map(|(at, _)| *at)
+            .collect::<Vec<_>>()
+            .into_iter();
+        let results = self
+            .expo
+            .send_push_notifications_stream(futures::stream::iter(
+                due.into_iter().map(|(_, message)| message),
+            ))
+            .collect::<Vec<_>>()
+            .await;
+        let mut held = vec![];
+        for result in &results {
+            let at = due_at.by_ref().take(result.messages.len());
+            if result.result.is_err() {
+                held.extend(at.zip(result.messages.iter().cloned()));
+            }
+        }
+        self.lock().extend(held);
+        results
+    }
+
+    /// Dispatches messages as they become due until none are held, checking at least every
+    /// `max_wait`. Stops at the first chunk that fails, whose messages stay held.
+    pub async fn run(&self, max_wait: Duration) -> Result<Vec<ExpoPushTicket>, CustomError> {
+        let mut tickets = vec![];
+        while let Some(due) = self.next_due() {
+            let wait = due
+                .duration_since(self.clock.now())
+                .unwrap_or_default()
+                .min(max_wait);
+            tokio::time::sleep(wait).await;
+            for chunk in self.dispatch_due().await {
+                tickets.extend(chunk.result?);
+            }
+        }
+        Ok(tickets)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(SystemTime, ExpoPushMessage)>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{expo_client::SplitChunkServer, ManualClock};
+
+    // 2024-01-15T02:30:00Z
+    const JAN_15_0230_UTC: u64 = 1_705_285_800;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_next_allowed() -> anyhow::Result<()> {
+        let night = QuietHours::new((22, 0), (8, 0))?;
+        let now = at(JAN_15_0230_UTC);
+        // 02:30 in London is quiet until 08:00.
+        assert_eq!(
+            night.next_allowed(now, "Europe/London")?,
+            at(JAN_15_0230_UTC + 5 * 3600 + 1800)
+        );
+        // 21:30 the day before in New York is allowed.
+        assert_eq!(night.next_allowed(now, "America/New_York")?, now);
+        // 05:30 in Moscow waits for 08:00 the same day.
+        assert_eq!(
+            night.next_allowed(now, "Europe/Moscow")?,
+            at(JAN_15_0230_UTC + 2 * 3600 + 1800)
+        );
+        // 23:30 the day before in São Paulo waits for 08:00 the next day.
+        assert_eq!(
+            night.next_allowed(now, "America/Sao_Paulo")?,
+            at(JAN_15_0230_UTC + 8 * 3600 + 1800)
+        );
+
+        let lunch = QuietHours::new((12, 0), (13, 0))?;
+        assert_eq!(lunch.next_allowed(now, "Europe/London")?, now);
+        assert!(night.next_allowed(now, "Mars/Olympus").is_err());
+        assert!(QuietHours::new((24, 0), (8, 0)).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_due() -> anyhow::Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/--/api/v2/push/send")
+            .match_body(r#"{"to":["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]}"#)
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{ "data": [{ "status": "ok", "id": "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX" }] }"#,
+            )
+            .expect(1)
+            .create();
+        let clock = Arc::new(ManualClock::new(at(JAN_15_0230_UTC)));
+        let scheduler =
+            Scheduler::with_clock(Expo::new_with_base_url(None, &server.url()), clock.clone());
+        let night = QuietHours::new((22, 0), (8, 0))?;
+
+        let due = scheduler.schedule(
+            ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+            "Europe/London",
+            &night,
+        )?;
+        assert_eq!(scheduler.dispatch_due().await, vec![]);
+        assert_eq!(scheduler.len(), 1);
+
+        clock.set(due);
+        let results = scheduler.dispatch_due().await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].result.as_ref().map(Vec::len), Ok(1));
+        assert!(scheduler.is_empty());
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_due_holds_failed_chunks() -> anyhow::Result<()> {
+        let server = SplitChunkServer::new().await;
+        let expo = server.expo();
+        let clock = Arc::new(ManualClock::new(at(JAN_15_0230_UTC)));
+        let scheduler = Scheduler::with_clock(expo, clock.clone());
+        let lunch = QuietHours::new((12, 0), (13, 0))?;
+        for _ in 0..SplitChunkServer::CHUNK_SIZE {
+            scheduler.schedule(
+                ExpoPushMessage::builder(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"]).build()?,
+                "Europe/London",
+                &lunch,
+            )?;
+        }
+        scheduler.schedule(
+            ExpoPushMessage::builder(["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"]).build()?,
+            "Europe/London",
+            &lunch,
+        )?;
+
+        let results = scheduler.dispatch_due().await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].result.is_ok());
+        assert!(results[1].result.is_err());
+        assert_eq!(scheduler.len(), 1);
+        assert_eq!(scheduler.next_due(), Some(at(JAN_15_0230_UTC)));
+        server.assert();
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{jsonl_store::JsonlFile, CustomError, ExpoPushTicket};
+
+/// Remembers the tickets of the messages sent with an idempotency key, see
+/// [`ExpoPushMessageBuilder::idempotency_key`](crate::ExpoPushMessageBuilder::idempotency_key).
+///
+/// Set [`ExpoClientOptions::sent_keys`](crate::ExpoClientOptions::sent_keys) to skip the
+/// messages whose key was already sent. Keys must outlive the process for a send retried
+/// after a crash to be skipped, so use [`FileSentKeyStore`] or another durable store there.
+///
+/// Keys are put once Expo accepted the chunk that carried them. A crash between that
+/// response and [`SentKeyStore::put`] leaves the key unrecorded, and the message is sent
+/// again.
+pub trait SentKeyStore: Send + Sync {
+    /// Returns the tickets recorded for `key`, unless they have expired at `now`.
+    fn get(&self, key: &str, now: SystemTime) -> Result<Option<Vec<ExpoPushTicket>>, CustomError>;
+
+    fn put(
+        &self,
+        key: &str,
+        tickets: Vec<ExpoPushTicket>,
+        now: SystemTime,
+    ) -> Result<(), CustomError>;
+}
+
+type SentKeys = HashMap<String, (SystemTime, Vec<ExpoPushTicket>)>;
+
+/// A [`SentKeyStore`] kept in memory that forgets keys after a time to live.
+///
+/// The keys are lost when the process exits, so it only protects against retries within
+/// the same process.
+#[derive(Debug)]
+pub struct MemorySentKeyStore {
+    ttl: Duration,
+    keys: Mutex<SentKeys>,
+}
+
+impl MemorySentKeyStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, SentKeys>, CustomError> {
+        self.keys
+            .lock()
+            .map_err(|e| CustomError::StoreErr(e.to_string()))
+    }
+}
+
+impl SentKeyStore for MemorySentKeyStore {
+    fn get(&self, key: &str, now: SystemTime) -> Result<Option<Vec<ExpoPushTicket>>, CustomError> {
+        Ok(self
+            .lock()?
+            .get(key)
+            .filter(|(sent_at, _)| *sent_at + self.ttl > now)
+            .map(|(_, tickets)| tickets.clone()))
+    }
+
+    fn put(
+        &self,
+        key: &str,
+        tickets: Vec<ExpoPushTicket>,
+        now: SystemTime,
+    ) -> Result<(), CustomError> {
+        let mut keys = self.lock()?;
+        keys.retain(|_, (sent_at, _)| *sent_at + self.ttl > now);
+        keys.insert(key.to_string(), (now, tickets));
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum FileEntry {
+    #[serde(rename_all = "camelCase")]
+    Put {
+        key: String,
+        sent_at: SystemTime,
+        tickets: Vec<ExpoPushTicket>,
+    },
+}
+
+/// A [`SentKeyStore`] backed by an append-only JSON Lines file, so that keys survive a
+/// crash.
+///
+/// Every key is appended to the file and replayed on [`FileSentKeyStore::open`]. The file
+/// is compacted down to the keys still alive once expired keys make up most of it.
+#[derive(Debug)]
+pub struct FileSentKeyStore {
+    file: JsonlFile,
+    keys: MemorySentKeyStore,
+}
+
+impl FileSentKeyStore {
+    pub fn open<P>(path: P, ttl: Duration) -> Result<Self, CustomError>
+    where
+        P: AsRef<Path>,
+    {
+        let keys = MemorySentKeyStore::new(ttl);
+        let file = JsonlFile::open(path.as_ref(), |entry| match entry {
+            FileEntry::Put {
+                key,
+                sent_at,
+                tickets,
+            } => keys.put(&key, tickets, sent_at),
+        })?;
+        Ok(Self { file, keys })
+    }
+}
+
+impl SentKeyStore for FileSentKeyStore {
+    fn get(&self, key: &str, now: SystemTime) -> Result<Option<Vec<ExpoPushTicket>>, CustomError> {
+        self.keys.get(key, now)
+    }
+
+    fn put(
+        &self,
+        key: &str,
+        tickets: Vec<ExpoPushTicket>,
+        now: SystemTime,
+    ) -> Result<(), CustomError> {
+        let mut file = self.file.lock()?;
+        file.append(&[FileEntry::Put {
+            key: key.to_string(),
+            sent_at: now,
+            tickets: tickets.clone(),
+        }])?;
+        self.keys.put(key, tickets, now)?;
+
+        let alive = self
+            .keys
+            .lock()?
+            .iter()
+            .map(|(key, (sent_at, tickets))| FileEntry::Put {
+                key: key.clone(),
+                sent_at: *sent_at,
+                tickets: tickets.clone(),
+            })
+            .collect::<Vec<_>>();
+        if file.lines() > 2 * alive.len() {
+            self.file.compact(&mut file, &alive)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonl_store::TempPath;
+
+    const T0: u64 = 1_700_000_000;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(T0 + secs)
+    }
+
+    fn assert_store(store: &dyn SentKeyStore) -> anyhow::Result<()> {
+        let tickets = vec![ExpoPushTicket::Unknown("pending".to_string())];
+
+        assert_eq!(store.get("a", at(0))?, None);
+        store.put("a", tickets.clone(), at(0))?;
+        assert_eq!(store.get("a", at(59))?, Some(tickets));
+        assert_eq!(store.get("a", at(60))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_sent_key_store() -> anyhow::Result<()> {
+        assert_store(&MemorySentKeyStore::new(Duration::from_secs(60)))
+    }
+
+    #[test]
+    fn test_file_sent_key_store() -> anyhow::Result<()> {
+        let path = TempPath::new("sent_keys.jsonl");
+        let ttl = Duration::from_secs(60);
+
+        assert_store(&FileSentKeyStore::open(&path, ttl)?)?;
+
+        // A key survives a reopen, and expired keys are compacted away.
+        let store = FileSentKeyStore::open(&path, ttl)?;
+        let tickets = vec![ExpoPushTicket::Unknown("pending".to_string())];
+        for (i, key) in ["b", "c", "d"].into_iter().enumerate() {
+            store.put(key, tickets.clone(), at(100 * i as u64))?;
+        }
+        let store = FileSentKeyStore::open(&path, ttl)?;
+        assert_eq!(store.get("d", at(200))?, Some(tickets));
+        assert_eq!(store.get("b", at(0))?, None);
+        assert_eq!(std::fs::read_to_string(&path)?.lines().count(), 2);
+
+        // The time to live runs from the original send, not from the reopen.
+        let store = FileSentKeyStore::open(&path, ttl)?;
+        assert_eq!(store.get("d", at(259))?.map(|t| t.len()), Some(1));
+        assert_eq!(store.get("d", at(260))?, None);
+        Ok(())
+    }
+}
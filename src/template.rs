@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{ExpoPushMessage, ValidationError};
+
+/// A message whose `title`, `subtitle`, `body` and `data` strings contain `{name}`
+/// placeholders, rendered into one message per recipient.
+///
+/// `{{` and `}}` stand for literal braces. Only string values of `data` are rendered, not
+/// its keys.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_message_template() -> anyhow::Result<()> {
+/// use std::collections::HashMap;
+/// use expo_push_notification_client::{ExpoPushMessage, MessageTemplate};
+///
+/// let template = MessageTemplate::new(
+///     ExpoPushMessage::builder(Vec::<String>::new())
+///         .title("Hi {name}")
+///         .body("You have {count} new messages")
+///         .build()?,
+/// )?;
+/// let variables = HashMap::from([
+///     ("name".to_string(), "Alice".to_string()),
+///     ("count".to_string(), "3".to_string()),
+/// ]);
+/// let message = template.render(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"], &variables)?;
+/// assert_eq!(message.title(), Some("Hi Alice"));
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    skeleton: ExpoPushMessage,
+}
+
+impl MessageTemplate {
+    /// The recipients of `skeleton` are ignored. Fails with
+    /// [`ValidationError::InvalidTemplate`] on an unclosed or empty placeholder.
+    pub fn new(skeleton: ExpoPushMessage) -> Result<Self, ValidationError> {
+        let template = Self { skeleton };
+        template.render_with(Vec::<String>::new(), |_| Some(""))?;
+        Ok(template)
+    }
+
+    /// Renders the message for `to`. Fails with [`ValidationError::MissingVariable`] when a
+    /// placeholder has no value in `variables`.
+    pub fn render<S, I>(
+        &self,
+        to: I,
+        variables: &HashMap<String, String>,
+    ) -> Result<ExpoPushMessage, ValidationError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.render_with(to, |name| variables.get(name).map(String::as_str))
+    }
+
+    fn render_with<'v, S, I, F>(&self, to: I, lookup: F) -> Result<ExpoPushMessage, ValidationError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        F: Fn(&str) -> Option<&'v str> + Copy,
+    {
+        let skeleton = &self.skeleton;
+        let mut builder = skeleton.to_builder().to(to);
+        if let Some(title) = skeleton.title() {
+            builder = builder.title(render_str(title, lookup)?);
+        }
+        if let Some(subtitle) = skeleton.subtitle() {
+            builder = builder.subtitle(render_str(subtitle, lookup)?);
+        }
+        if let Some(body) = skeleton.body() {
+            builder = builder.body(render_str(body, lookup)?);
+        }
+        if let Some(data) = skeleton.data() {
+            builder = builder.data(&render_value(data, lookup)?)?;
+        }
+        builder.build()
+    }
+}
+
+fn render_value<'v, F>(value: &Value, lookup: F) -> Result<Value, ValidationError>
+where
+    F: Fn(&str) -> Option<&'v str> + Copy,
+{
+    Ok(match value {
+        Value::String(s) => Value::String(render_str(s, lookup)?),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| render_value(value, lookup))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| Ok((key.clone(), render_value(value, lookup)?)))
+                .collect::<Result<_, ValidationError>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+fn render_str<'v, F>(template: &str, lookup: F) -> Result<String, ValidationError>
+where
+    F: Fn(&str) -> Option<&'v str>,
+{
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        rendered.push_str(&rest[..start]);
+        let brace = &rest[start..start + 1];
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            rendered.push_str(brace);
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            return Err(ValidationError::InvalidTemplate);
+        }
+        let end = rest
+            .find(['{', '}'])
+            .ok_or(ValidationError::InvalidTemplate)?;
+        let name = &rest[..end];
+        if name.is_empty() || &rest[end..end + 1] == "{" {
+            return Err(ValidationError::InvalidTemplate);
+        }
+        rendered.push_str(
+            lookup(name).ok_or_else(|| ValidationError::MissingVariable(name.to_string()))?,
+        );
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([
+            ("name".to_string(), "Alice".to_string()),
+            ("count".to_string(), "3".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_render() -> anyhow::Result<()> {
+        let template = MessageTemplate::new(
+            ExpoPushMessage::builder(Vec::<String>::new())
+                .title("Hi {name}")
+                .subtitle("{{literal}}")
+                .body("{count} new")
+                .data(&serde_json::json!({ "user": "{name}", "items": ["{count}", 1] }))?
+                .badge(1)
+                .build()?,
+        )?;
+        let message =
+            template.render(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"], &variables())?;
+        assert_eq!(
+            serde_json::to_value(&message)?,
+            serde_json::json!({
+                "to": ["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"],
+                "title": "Hi Alice",
+                "subtitle": "{literal}",
+                "body": "3 new",
+                "data": { "user": "Alice", "items": ["3", 1] },
+                "badge": 1
+            })
+        );
+        assert_eq!(
+            template.render(["invalid"], &variables()),
+            Err(ValidationError::InvalidToken)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> anyhow::Result<()> {
+        let template = MessageTemplate::new(
+            ExpoPushMessage::builder(Vec::<String>::new())
+                .body("{missing}")
+                .build()?,
+        )?;
+        assert_eq!(
+            template.render(["ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]"], &variables()),
+            Err(ValidationError::MissingVariable("missing".to_string()))
+        );
+
+        for body in ["{name", "name}", "{}", "{na{me}", "}}}"] {
+            assert_eq!(
+                MessageTemplate::new(
+                    ExpoPushMessage::builder(Vec::<String>::new())
+                        .body(body)
+                        .build()?
+                ),
+                Err(ValidationError::InvalidTemplate),
+                "{body} should be rejected"
+            );
+        }
+        Ok(())
+    }
+}
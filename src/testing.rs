@@ -0,0 +1,424 @@
+//! A fake Expo push service for integration tests, enabled by the `testing` feature.
+
+use std::collections::HashMap;
+use std::io::Read as _;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::{DetailsErrorType, Expo};
+
+// Expo accepts at most 100 messages per request.
+const MAX_MESSAGES: usize = 100;
+// Expo rejects notifications whose payload exceeds 4096 bytes.
+const MAX_PAYLOAD_BYTES: usize = 4096;
+
+/// What the fake server answers for a token.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum FakeOutcome {
+    /// An ok ticket and an ok receipt.
+    #[default]
+    Delivered,
+    /// An error ticket with the given `details.error`.
+    TicketError(DetailsErrorType),
+    /// An ok ticket and an error receipt with the given `details.error`.
+    ReceiptError(DetailsErrorType),
+}
+
+/// A fake Expo push service listening on localhost.
+///
+/// It implements `/--/api/v2/push/send` and `/--/api/v2/push/getReceipts`:
+///
+/// - malformed tokens get a `DeviceNotRegistered` ticket,
+/// - notifications over 4096 bytes get a `MessageTooBig` ticket,
+/// - tokens over the rate limit get a `MessageRateExceeded` ticket,
+/// - other tokens get the outcome set with [`FakeExpoServer::set_outcome`],
+/// - requests with more than 100 messages are rejected with status 400,
+/// - gzip-compressed bodies are decoded.
+///
+/// The server stops when dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_fake_expo_server() -> anyhow::Result<()> {
+/// use expo_push_notification_client::{
+///     testing::{FakeExpoServer, FakeOutcome},
+///     DetailsErrorType, Expo, ExpoPushMessage, ExpoPushTicket,
+/// };
+///
+/// let server = FakeExpoServer::start().await?;
+/// server.set_outcome(
+///     "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+///     FakeOutcome::TicketError(DetailsErrorType::DeviceNotRegistered),
+/// );
+/// let expo = Expo::new_with_base_url(None, &server.url());
+/// let tickets = expo
+///     .send_push_notifications(
+///         ExpoPushMessage::builder([
+///             "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+///             "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+///         ])
+///         .build()?,
+///     )
+///     .await?;
+/// assert!(matches!(tickets[..], [ExpoPushTicket::Ok(_), ExpoPushTicket::Error(_)]));
+/// assert_eq!(server.received_messages().len(), 1);
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FakeExpoServer {
+    url: String,
+    state: Arc<Mutex<State>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    outcomes: HashMap<String, FakeOutcome>,
+    rate_limit: Option<(usize, Duration)>,
+    sent_at: HashMap<String, Vec<Instant>>,
+    receipts: HashMap<String, Value>,
+    next_receipt_id: u64,
+    received: Vec<Value>,
+}
+
+impl FakeExpoServer {
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        // A broken connection only fails the request of the client.
+                        let _ = serve(stream, &state).await;
+                    });
+                }
+            }
+        });
+        Ok(Self { url, state, task })
+    }
+
+    /// The base url to pass to [`Expo::new_with_base_url`].
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn set_outcome<S>(&self, token: S, outcome: FakeOutcome)
+    where
+        S: Into<String>,
+    {
+        lock(&self.state).outcomes.insert(token.into(), outcome);
+    }
+
+    /// Accepts at most `max` notifications per token within `window`.
+    pub fn set_rate_limit(&self, max: usize, window: Duration) {
+        lock(&self.state).rate_limit = Some((max, window));
+    }
+
+    /// Every message received so far, as sent.
+    pub fn received_messages(&self) -> Vec<Value> {
+        lock(&self.state).received.clone()
+    }
+}
+
+impl Drop for FakeExpoServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    // A panicking test must not hide the state from the others.
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+async fn serve(stream: TcpStream, state: &Mutex<State>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut content_length = 0;
+    let mut gzip = false;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "content-encoding" => gzip = value.trim() == "gzip",
+                _ => {}
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    if gzip {
+        let mut decoded = vec![];
+        flate2::read::GzDecoder::new(body.as_slice()).read_to_end(&mut decoded)?;
+        body = decoded;
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, response) = match serde_json::from_slice::<Value>(&body) {
+        Err(e) => (400, error_response("VALIDATION_ERROR", &e.to_string())),
+        Ok(body) => match path {
+            "/--/api/v2/push/send" => send(body, &mut lock(state)),
+            "/--/api/v2/push/getReceipts" => get_receipts(body, &lock(state)),
+            _ => (404, error_response("NOT_FOUND", "Not found")),
+        },
+    };
+
+    let response = response.to_string();
+    let mut stream = reader.into_inner();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status} {}\r\ncontent-type: application/json; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+                if status == 200 { "OK" } else { "Error" },
+                response.len(),
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await
+}
+
+fn error_response(code: &str, message: &str) -> Value {
+    json!({ "errors": [{ "code": code, "message": message }] })
+}
+
+fn send(body: Value, state: &mut State) -> (u16, Value) {
+    let messages = match body {
+        Value::Array(messages) => messages,
+        message => vec![message],
+    };
+    if messages.len() > MAX_MESSAGES {
+        return (
+            400,
+            error_response(
+                "PUSH_TOO_MANY_NOTIFICATIONS",
+                "You are trying to send more than 100 push notifications in one request",
+            ),
+        );
+    }
+
+    let mut tickets = vec![];
+    for message in messages {
+        let tokens = match message.get("to") {
+            Some(Value::String(token)) => vec![token.clone()],
+            Some(Value::Array(tokens)) => tokens
+                .iter()
+                .filter_map(|token| token.as_str().map(str::to_string))
+                .collect(),
+            _ => vec![],
+        };
+        let mut payload = message.clone();
+        if let Some(payload) = payload.as_object_mut() {
+            payload.remove("to");
+        }
+        let too_big = payload.to_string().len() > MAX_PAYLOAD_BYTES;
+        state.received.push(message);
+
+        for token in tokens {
+            tickets.push(if !Expo::is_expo_push_token(&token) {
+                error_ticket(
+                    format!("\"{token}\" is not a valid Expo push token"),
+                    DetailsErrorType::DeviceNotRegistered,
+                    Some(&token),
+                )
+            } else if too_big {
+                error_ticket(
+                    "Message too big".to_string(),
+                    DetailsErrorType::MessageTooBig,
+                    None,
+                )
+            } else if state.is_rate_limited(&token) {
+                error_ticket(
+                    "Message rate exceeded".to_string(),
+                    DetailsErrorType::MessageRateExceeded,
+                    None,
+                )
+            } else {
+                match state.outcomes.get(&token).cloned().unwrap_or_default() {
+                    FakeOutcome::Delivered => state.ok_ticket(json!({ "status": "ok" })),
+                    FakeOutcome::TicketError(error) => error_ticket(
+                        format!("\"{token}\" failed with {}", error.as_str()),
+                        error,
+                        Some(&token),
+                    ),
+                    FakeOutcome::ReceiptError(error) => state.ok_ticket(error_ticket(
+                        format!("\"{token}\" failed with {}", error.as_str()),
+                        error,
+                        Some(&token),
+                    )),
+                }
+            });
+        }
+    }
+    (200, json!({ "data": tickets }))
+}
+
+fn error_ticket(message: String, error: DetailsErrorType, token: Option<&str>) -> Value {
+    let mut details = json!({ "error": error.as_str() });
+    if let Some(token) = token {
+        details["expoPushToken"] = json!(token);
+    }
+    json!({ "status": "error", "message": message, "details": details })
+}
+
+impl State {
+    fn is_rate_limited(&mut self, token: &str) -> bool {
+        let Some((max, window)) = self.rate_limit else {
+            return false;
+        };
+        let now = Instant::now();
+        let sent_at = self.sent_at.entry(token.to_string()).or_default();
+        sent_at.retain(|sent_at| now.duration_since(*sent_at) < window);
+        if sent_at.len() >= max {
+            return true;
+        }
+        sent_at.push(now);
+        false
+    }
+
+    // Issues a receipt id for `receipt` and returns the ok ticket carrying it.
+    fn ok_ticket(&mut self, receipt: Value) -> Value {
+        self.next_receipt_id += 1;
+        let id = format!("00000000-0000-4000-8000-{:012X}", self.next_receipt_id);
+        self.receipts.insert(id.clone(), receipt);
+        json!({ "status": "ok", "id": id })
+    }
+}
+
+fn get_receipts(body: Value, state: &State) -> (u16, Value) {
+    let Some(ids) = body.get("ids").and_then(Value::as_array) else {
+        return (
+            400,
+            error_response("VALIDATION_ERROR", "\"ids\" is required"),
+        );
+    };
+    let receipts = ids
+        .iter()
+        .filter_map(Value::as_str)
+        .filter_map(|id| Some((id.to_string(), state.receipts.get(id)?.clone())))
+        .collect::<serde_json::Map<_, _>>();
+    (200, json!({ "data": receipts }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExpoClientOptions, ExpoPushMessage, ExpoPushReceipt, ExpoPushTicket, GzipPolicy};
+
+    fn error_of(ticket: &ExpoPushTicket) -> Option<DetailsErrorType> {
+        match ticket {
+            ExpoPushTicket::Error(error) => error.details.as_ref()?.error.clone(),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_and_get_receipts() -> anyhow::Result<()> {
+        let server = FakeExpoServer::start().await?;
+        server.set_outcome(
+            "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+            FakeOutcome::ReceiptError(DetailsErrorType::DeviceNotRegistered),
+        );
+        server.set_outcome(
+            "ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]",
+            FakeOutcome::TicketError(DetailsErrorType::InvalidCredentials),
+        );
+        let expo = Expo::new(ExpoClientOptions {
+            base_url: Some(server.url()),
+            gzip: GzipPolicy::Always,
+            ..Default::default()
+        });
+
+        let message = ExpoPushMessage::builder([
+            "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]",
+            "ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]",
+            "ExponentPushToken[zzzzzzzzzzzzzzzzzzzzzz]",
+        ])
+        .title("Hello")
+        .build()?;
+        let tickets = expo.send_push_notifications(&message).await?;
+        assert_eq!(
+            tickets.iter().map(error_of).collect::<Vec<_>>(),
+            [None, None, Some(DetailsErrorType::InvalidCredentials)]
+        );
+        assert_eq!(
+            server.received_messages(),
+            [serde_json::to_value(&message)?]
+        );
+
+        let ids = tickets.iter().filter_map(|ticket| match ticket {
+            ExpoPushTicket::Ok(ticket) => Some(ticket.id.clone()),
+            _ => None,
+        });
+        let ids = ids.collect::<Vec<_>>();
+        let receipts = expo.get_push_notification_receipts(ids.clone()).await?;
+        assert_eq!(receipts[&ids[0]], ExpoPushReceipt::Ok);
+        assert!(matches!(
+            &receipts[&ids[1]],
+            ExpoPushReceipt::Error(error)
+                if error.details.as_ref().and_then(|d| d.expo_push_token.as_deref())
+                    == Some("ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]")
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limits() -> anyhow::Result<()> {
+        let server = FakeExpoServer::start().await?;
+        server.set_rate_limit(1, Duration::from_secs(60));
+        let expo = Expo::new_with_base_url(None, &server.url());
+
+        let token = "ExponentPushToken[xxxxxxxxxxxxxxxxxxxxxx]";
+        let tickets = expo
+            .send_push_notifications([
+                ExpoPushMessage::builder([token]).build()?,
+                ExpoPushMessage::builder([token]).build()?,
+                ExpoPushMessage::builder(["ExponentPushToken[yyyyyyyyyyyyyyyyyyyyyy]"])
+                    .body("x".repeat(MAX_PAYLOAD_BYTES))
+                    .build()?,
+            ])
+            .await?;
+        assert_eq!(
+            tickets.iter().map(error_of).collect::<Vec<_>>(),
+            [
+                None,
+                Some(DetailsErrorType::MessageRateExceeded),
+                Some(DetailsErrorType::MessageTooBig)
+            ]
+        );
+
+        let raw = reqwest::Client::new()
+            .post(format!("{}/--/api/v2/push/send", server.url()))
+            .json(&json!({ "to": ["not a token"] }))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        assert_eq!(raw["data"][0]["details"]["error"], "DeviceNotRegistered");
+
+        let too_many = vec![json!({ "to": token }); MAX_MESSAGES + 1];
+        let status = reqwest::Client::new()
+            .post(format!("{}/--/api/v2/push/send", server.url()))
+            .json(&too_many)
+            .send()
+            .await?
+            .status();
+        assert_eq!(status, 400);
+        Ok(())
+    }
+}
@@ -22,6 +22,9 @@ async fn test() -> anyhow::Result<()> {
             ExpoPushTicket::Error(e) => {
                 eprintln!("send error: {:?}", e);
             }
+            ExpoPushTicket::Unknown(status) => {
+                eprintln!("unknown ticket status: {}", status);
+            }
         }
     }
 
@@ -36,6 +39,9 @@ async fn test() -> anyhow::Result<()> {
                 ExpoPushReceipt::Error(e) => {
                     eprintln!("get receipt error: {:?}", e);
                 }
+                ExpoPushReceipt::Unknown(status) => {
+                    eprintln!("unknown receipt status: {}", status);
+                }
             },
             None => {
                 eprintln!("receipt not found for id: {}", id);